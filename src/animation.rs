@@ -0,0 +1,135 @@
+use std::{ fs::{ create_dir_all, File }, path::Path };
+
+use image::{
+    codecs::gif::{ GifEncoder, Repeat },
+    imageops::overlay,
+    Delay,
+    DynamicImage,
+    Frame,
+    ImageResult,
+    Rgb,
+    RgbImage,
+};
+use rand::Rng;
+
+use crate::{
+    config::{ get_output_directory, AnimationConfig, Config },
+    contact_sheet::{ draw_text, get_line_height },
+    global_data::GlobalData,
+    individual::Individual,
+    objectives::ObjectiveDirection,
+};
+
+const FONT_SCALE: u32 = 2;
+// speed of the color quantization of the gif encoder, from 1 (best quality) to 30 (fastest)
+const GIF_ENCODER_SPEED: i32 = 10;
+
+/**
+ * Index of the knee point of the front. The objectives are normalized to 0 (best) to 1 (worst) over the front and the
+ * knee point is the individual closest to the ideal point, where every objective is 0.
+ */
+pub fn get_knee_point_index(front: &[Individual], global_data: &GlobalData) -> usize {
+    let number_of_objectives = global_data.objectives.len();
+    let mut minimums = vec![f64::INFINITY; number_of_objectives];
+    let mut maximums = vec![f64::NEG_INFINITY; number_of_objectives];
+    for individual in front.iter() {
+        for (index, value) in individual.get_objectives().iter().enumerate() {
+            minimums[index] = minimums[index].min(*value);
+            maximums[index] = maximums[index].max(*value);
+        }
+    }
+
+    let get_distance_to_ideal_point = |individual: &Individual| {
+        individual
+            .get_objectives()
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let range = maximums[index] - minimums[index];
+                if range <= 0.0 {
+                    return 0.0;
+                }
+                let normalized = match global_data.objectives[index].direction {
+                    ObjectiveDirection::Maximize => (maximums[index] - value) / range,
+                    ObjectiveDirection::Minimize => (value - minimums[index]) / range,
+                };
+                normalized * normalized
+            })
+            .sum::<f64>()
+    };
+    (0..front.len())
+        .min_by(|a, b| {
+            get_distance_to_ideal_point(&front[*a]).partial_cmp(&get_distance_to_ideal_point(&front[*b])).unwrap()
+        })
+        .unwrap()
+}
+
+/**
+ * Renders one frame: the input image with the segment borders of the best individual of every objective and of the
+ * knee point next to each other, labelled with the generation
+ */
+pub fn get_animation_frame(front: &[Individual], generation: usize, global_data: &GlobalData) -> RgbImage {
+    let mut individuals: Vec<(String, &Individual)> = vec![];
+    for (index, weighted_objective) in global_data.objectives.iter().enumerate() {
+        let best_individual = front
+            .iter()
+            .reduce(|best, individual| {
+                if weighted_objective.direction.is_better(individual.get_objectives()[index], best.get_objectives()[index]) {
+                    individual
+                } else {
+                    best
+                }
+            })
+            .unwrap();
+        individuals.push((format!("best {}", weighted_objective.objective.name()), best_individual));
+    }
+    individuals.push(("knee point".to_string(), &front[get_knee_point_index(front, global_data)]));
+
+    let image_width = global_data.width as u32;
+    let image_height = global_data.height as u32;
+    let line_height = get_line_height(FONT_SCALE);
+    let mut frame = RgbImage::from_pixel(
+        (individuals.len() as u32) * (image_width + line_height),
+        image_height + 2 * line_height,
+        Rgb([255, 255, 255])
+    );
+    draw_text(&mut frame, 0, 0, &format!("generation {}", generation), FONT_SCALE, Rgb([0, 0, 0]));
+    for (index, (label, individual)) in individuals.iter().enumerate() {
+        let x = (index as u32) * (image_width + line_height);
+        draw_text(&mut frame, x, line_height, label, FONT_SCALE, Rgb([0, 0, 0]));
+        overlay(
+            &mut frame,
+            &individual.get_segment_border_image_inline(global_data),
+            x as i64,
+            (2 * line_height) as i64
+        );
+    }
+    frame
+}
+
+/**
+ * Saves the frames as a looping gif to the output directory
+ */
+pub fn export_animation(
+    frames: &[RgbImage],
+    animation_config: &AnimationConfig,
+    config: &Config
+) -> ImageResult<()> {
+    let output_directory = get_output_directory(config);
+    create_dir_all(&output_directory)?;
+    let path = Path::new(&output_directory).join(format!("animation_{}.gif", rand::thread_rng().gen::<u32>()));
+    let mut encoder = GifEncoder::new_with_speed(File::create(&path)?, GIF_ENCODER_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(
+        frames.iter().map(|frame| {
+            Frame::from_parts(
+                DynamicImage::ImageRgb8(frame.clone()).to_rgba8(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(animation_config.frame_delay_milliseconds, 1)
+            )
+        })
+    )?;
+    println!("Exported the animation to {:?}", path);
+    Ok(())
+}
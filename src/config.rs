@@ -8,6 +8,10 @@ pub struct FunctionConfig {
     #[serde(default)]
     pub probability: Option<f64>,
 
+    // "count" (default), "per_individual" or "per_gene". "per_gene" is only supported by the single gene mutations
+    #[serde(default)]
+    pub probability_mode: Option<String>,
+
     #[serde(default)]
     pub tournament_size: Option<usize>,
 
@@ -24,7 +28,233 @@ pub struct FunctionConfig {
     pub max_depth_percent_of_picture: Option<f64>,
 
     #[serde(default)]
-    pub minimum_coverage_percentage: Option<f64>
+    pub minimum_coverage_percentage: Option<f64>,
+
+    #[serde(default)]
+    pub minimum_segment_size: Option<usize>,
+
+    // number of whole segments which are copied by the segment preserving crossover
+    #[serde(default)]
+    pub number_of_segments: Option<usize>,
+
+    // probability that the uniform crossover swaps a gene, independent of the probability mode. Defaults to 0.5
+    #[serde(default)]
+    pub swap_probability: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ObjectiveConfig {
+    pub name: String,
+
+    // "maximize" or "minimize". Defaults to the natural direction of the objective
+    #[serde(default)]
+    pub direction: Option<String>,
+
+    // multiplier for the weighted fitness
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConstraintConfig {
+    #[serde(default)]
+    pub minimum_number_of_segments: Option<usize>,
+
+    #[serde(default)]
+    pub maximum_number_of_segments: Option<usize>,
+
+    // minimum number of pixels every segment needs to have
+    #[serde(default)]
+    pub minimum_segment_size: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TextureConfig {
+    // radius of the square window the texture features are aggregated over
+    pub window_radius: usize,
+
+    // weight of the color distance. Set it to 0 to substitute the color by the texture
+    #[serde(default = "default_color_weight")]
+    pub color_weight: f64,
+
+    #[serde(default)]
+    pub deviation_weight: f64,
+
+    #[serde(default)]
+    pub edge_weight: f64,
+}
+
+fn default_color_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdaptiveOperatorSelectionConfig {
+    // "probability_matching" or "adaptive_pursuit"
+    pub method: String,
+
+    // lower bound of the share of every operator
+    #[serde(default = "default_minimum_operator_probability")]
+    pub minimum_probability: f64,
+
+    // how fast the quality of an operator follows its rewards
+    #[serde(default = "default_operator_adaptation_rate")]
+    pub adaptation_rate: f64,
+
+    // how fast adaptive pursuit moves the probabilities towards the best operator
+    #[serde(default = "default_operator_adaptation_rate")]
+    pub learning_rate: f64,
+}
+
+fn default_minimum_operator_probability() -> f64 {
+    0.05
+}
+
+fn default_operator_adaptation_rate() -> f64 {
+    0.3
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SelfAdaptationConfig {
+    // every parameter is multiplied by exp(learning_rate * N(0, 1)) once per generation
+    #[serde(default = "default_self_adaptation_learning_rate")]
+    pub learning_rate: f64,
+}
+
+fn default_self_adaptation_learning_rate() -> f64 {
+    0.2
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PreprocessingStepConfig {
+    pub name: String,
+
+    // gaussian blur
+    #[serde(default)]
+    pub sigma: Option<f32>,
+
+    // canny hysteresis thresholds
+    #[serde(default)]
+    pub low: Option<f32>,
+
+    #[serde(default)]
+    pub high: Option<f32>,
+
+    // median filter and morphological operations
+    #[serde(default)]
+    pub radius: Option<u32>,
+
+    // "l1" or "linf" for the morphological operations
+    #[serde(default)]
+    pub norm: Option<String>,
+
+    #[serde(default)]
+    pub offset: Option<u8>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PreprocessingConfig {
+    // applied to the image before the colors are used in the objectives, e.g. to denoise it
+    #[serde(default)]
+    pub rgb_steps: Vec<PreprocessingStepConfig>,
+
+    // creates the map the mutation weights are calculated from, starting with the grayscale of the preprocessed
+    // rgb image. If not set canny, blur, offset and normalize are used with the canny and blur settings above
+    #[serde(default)]
+    pub edge_steps: Option<Vec<PreprocessingStepConfig>>,
+
+    // saves the image after every step to ./logs/preprocessing/<problem_instance>
+    #[serde(default)]
+    pub export_intermediate_images: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PyramidConfig {
+    // number of levels including the full resolution. Every level halves the width and height
+    pub number_of_levels: usize,
+
+    // generations on every coarse level. The full resolution uses number_of_generations
+    pub number_of_generations_per_coarse_level: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MemeticConfig {
+    // the hill climb runs every interval generations
+    pub interval: usize,
+
+    // number of random non-dominated individuals which are refined
+    #[serde(default = "default_memetic_number_of_individuals")]
+    pub number_of_individuals: usize,
+
+    // maximum number of objective evaluations of one memetic phase
+    pub evaluation_budget: usize,
+}
+
+fn default_memetic_number_of_individuals() -> usize {
+    1
+}
+
+// the parameters of every individual are sampled uniformly from the given ranges
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SlicConfig {
+    pub minimum_number_of_superpixels: usize,
+    pub maximum_number_of_superpixels: usize,
+
+    // weight of the spatial distance compared to the color distance
+    pub minimum_compactness: f64,
+    pub maximum_compactness: f64,
+
+    #[serde(default = "default_slic_iterations")]
+    pub number_of_iterations: usize,
+}
+
+fn default_slic_iterations() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnimationConfig {
+    // a frame is recorded every interval generations and after the last generation
+    pub interval: usize,
+
+    #[serde(default = "default_animation_frame_delay")]
+    pub frame_delay_milliseconds: u32,
+}
+
+fn default_animation_frame_delay() -> u32 {
+    500
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ContactSheetConfig {
+    // name of the objective the individuals are sorted by, e.g. "edge_value". Sorted from the best to the worst value
+    pub sort_objective: String,
+
+    #[serde(default = "default_contact_sheet_number_of_columns")]
+    pub number_of_columns: usize,
+
+    // every pixel of the 3x5 font is drawn as a square with this size
+    #[serde(default = "default_contact_sheet_font_scale")]
+    pub font_scale: u32,
+}
+
+fn default_contact_sheet_number_of_columns() -> usize {
+    2
+}
+
+fn default_contact_sheet_font_scale() -> u32 {
+    2
+}
+
+// the scale of every individual is sampled uniformly from the given range
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FelzenszwalbConfig {
+    // bigger scales prefer bigger segments
+    pub minimum_scale: f64,
+    pub maximum_scale: f64,
+
+    #[serde(default)]
+    pub minimum_segment_size: usize,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -46,7 +276,143 @@ pub struct Config {
     pub canny_hysteresis_low: f32,
     pub blur_sigma: f32,
     pub show_images: bool,
-    pub number_of_threads: usize
+    pub number_of_threads: usize,
+
+    #[serde(default = "default_color_space")]
+    pub color_space: String,
+
+    #[serde(default = "default_distance_metric")]
+    pub distance_metric: String,
+
+    #[serde(default)]
+    pub texture: Option<TextureConfig>,
+
+    // if not set the edge value, connectivity and overall deviation are used with the multipliers above
+    #[serde(default)]
+    pub objectives: Option<Vec<ObjectiveConfig>>,
+
+    // infeasible individuals are ranked behind feasible ones (constrained domination)
+    #[serde(default)]
+    pub constraints: Option<ConstraintConfig>,
+
+    #[serde(default)]
+    pub preprocessing: Option<PreprocessingConfig>,
+
+    // If set, the genetic algorithm runs on downscaled images first and the final population of every level
+    // is upsampled to the initial population of the next finer level
+    #[serde(default)]
+    pub pyramid: Option<PyramidConfig>,
+
+    // If set, the probabilities of the crossovers and mutations are adapted to the survival of their offspring
+    #[serde(default)]
+    pub adaptive_operator_selection: Option<AdaptiveOperatorSelectionConfig>,
+
+    // If set, every individual carries its own parameters for eat_similar, flip_to_smallest_deviation and
+    // destroy_small_segments, which start at the configured values and mutate with the individual
+    #[serde(default)]
+    pub self_adaptation: Option<SelfAdaptationConfig>,
+
+    // If set, non-dominated individuals are refined by a local search every few generations
+    #[serde(default)]
+    pub memetic: Option<MemeticConfig>,
+
+    // probability that the single gene mutations pick a pixel on the segment boundaries of the child instead of
+    // an edge weighted pixel. 0 disables it
+    #[serde(default)]
+    pub boundary_sampling_weight: f64,
+
+    // "none" or "neighbor". If set, connections pointing outside of the image are repaired after the crossover
+    // and after the mutation
+    #[serde(default)]
+    pub repair_method: Option<String>,
+
+    // the individuals of the mst initialization remove between 0 and this number of the heaviest MST edges
+    #[serde(default = "default_mst_maximum_removed_edges")]
+    pub mst_maximum_removed_edges: usize,
+
+    // MST edges which would cut off a segment with less pixels are never removed
+    #[serde(default = "default_mst_minimum_segment_size")]
+    pub mst_minimum_segment_size: usize,
+
+    // only needed for the slic initialization
+    #[serde(default)]
+    pub slic: Option<SlicConfig>,
+
+    // only needed for the felzenszwalb initialization
+    #[serde(default)]
+    pub felzenszwalb: Option<FelzenszwalbConfig>,
+
+    // path of the image to segment. Defaults to the test image of the problem instance in the training images
+    #[serde(default)]
+    pub input_image_path: Option<String>,
+
+    // if set, the exported segmentations are compared with the ground truth images in this directory
+    #[serde(default)]
+    pub ground_truth_directory: Option<String>,
+
+    // defaults to ./logs/result_segmentation/<problem_instance>
+    #[serde(default)]
+    pub output_directory: Option<String>,
+
+    // writes the results with their objective values to an html gallery in the output directory, which also works
+    // without the gui feature
+    #[serde(default)]
+    pub export_gallery: bool,
+
+    // If set, the best individual of every objective and the knee point of the front are recorded during the run and
+    // saved as an animated gif in the output directory. With a pyramid only the full resolution is recorded
+    #[serde(default)]
+    pub animation: Option<AnimationConfig>,
+
+    // "palette", "mean_color", "graph" or "random_hsv". How the segments are colored in the exported images
+    #[serde(default = "default_segment_coloring")]
+    pub segment_coloring: String,
+
+    // seed of the random colors of "random_hsv" and of the additional colors of "graph"
+    #[serde(default)]
+    pub segment_coloring_seed: u64,
+
+    // If set, all exported individuals are rendered into one labelled grid image in the output directory
+    #[serde(default)]
+    pub contact_sheet: Option<ContactSheetConfig>,
+
+    // segments with less pixels are merged into their most similar neighbor before exporting. 0 disables it
+    #[serde(default)]
+    pub post_processing_minimum_segment_size: usize,
+}
+
+fn default_mst_maximum_removed_edges() -> usize {
+    50
+}
+
+fn default_mst_minimum_segment_size() -> usize {
+    1
+}
+
+fn default_segment_coloring() -> String {
+    "palette".to_string()
+}
+
+fn default_color_space() -> String {
+    "rgb".to_string()
+}
+
+fn default_distance_metric() -> String {
+    "euclidean".to_string()
+}
+
+pub fn get_input_image_path(config: &Config) -> String {
+    match &config.input_image_path {
+        Some(input_image_path) => input_image_path.clone(),
+        None => format!("./Project 3 training_images/{}/Test image.jpg", config.problem_instance),
+    }
+}
+
+pub fn get_output_directory(config: &Config) -> String {
+    match &config.output_directory {
+        Some(output_directory) => output_directory.clone(),
+        None => format!("./logs/result_segmentation/{}", config.problem_instance),
+    }
 }
 
 pub fn initialize_config(file_path: &str) -> Config {
@@ -59,3 +425,34 @@ pub fn initialize_config(file_path: &str) -> Config {
         }
     }
 }
+
+/**
+ * Config with all optional features disabled for the tests
+ */
+#[cfg(test)]
+pub fn get_test_config() -> Config {
+    serde_json
+        ::from_str(
+            r#"{
+                "export_pareto_front": true,
+                "problem_instance": "test",
+                "population_size": 2,
+                "number_of_generations": 1,
+                "initialization_method": "mst",
+                "parent_selection": { "name": "tournament", "tournament_size": 2, "probability": 1.0 },
+                "crossovers": [],
+                "mutations": [],
+                "survivor_selection": { "name": "NSGA-2", "combine_parents_and_offspring": true },
+                "preserve_skyline": false,
+                "edge_value_multiplier": 1.0,
+                "connectivity_multiplier": 1.0,
+                "overall_deviation_multiplier": 1.0,
+                "canny_hysteresis_high": 40.0,
+                "canny_hysteresis_low": 28.0,
+                "blur_sigma": 1.0,
+                "show_images": false,
+                "number_of_threads": 1
+            }"#
+        )
+        .unwrap()
+}
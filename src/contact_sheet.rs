@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use image::{ imageops::overlay, ImageResult, Rgb, RgbImage };
+use rand::Rng;
+
+use crate::{
+    config::{ get_output_directory, Config, ContactSheetConfig },
+    global_data::GlobalData,
+    individual::Individual,
+    objectives::ObjectiveDirection,
+};
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+// space between the images, cells and lines in font pixels
+const SPACING: u32 = 2;
+
+/**
+ * Returns the rows of a 3x5 glyph from top to bottom, the highest of the three bits is the left pixel.
+ * Lowercase letters are drawn as uppercase and unknown characters as a question mark.
+ */
+fn get_glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/**
+ * Height of a line of text including the space to the next line
+ */
+pub fn get_line_height(scale: u32) -> u32 {
+    (GLYPH_HEIGHT + SPACING) * scale
+}
+
+/**
+ * Draws the text with its top left corner at (x, y). Pixels outside of the image are skipped.
+ */
+pub fn draw_text(image: &mut RgbImage, x: u32, y: u32, text: &str, scale: u32, color: Rgb<u8>) {
+    for (index, character) in text.chars().enumerate() {
+        let glyph_x = x + (index as u32) * (GLYPH_WIDTH + 1) * scale;
+        for (glyph_row, bits) in get_glyph(character).iter().enumerate() {
+            for glyph_column in 0..GLYPH_WIDTH {
+                if (bits >> (GLYPH_WIDTH - 1 - glyph_column)) & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let pixel_x = glyph_x + glyph_column * scale + dx;
+                        let pixel_y = y + (glyph_row as u32) * scale + dy;
+                        if pixel_x < image.width() && pixel_y < image.height() {
+                            image.put_pixel(pixel_x, pixel_y, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Label lines of an individual: its rank, every objective value and the number of segments
+ */
+fn get_labels(rank: usize, individual: &Individual, global_data: &GlobalData) -> Vec<String> {
+    let mut labels = vec![format!("#{}", rank + 1)];
+    for (weighted_objective, value) in global_data.objectives
+        .iter()
+        .zip(individual.get_objectives().iter()) {
+        labels.push(format!("{}: {:.2}", weighted_objective.objective.name(), value));
+    }
+    // cluster ids are assigned consecutively starting with 1
+    let number_of_segments = individual
+        .get_cluster_map(global_data.width as i64, global_data.height as i64)
+        .iter()
+        .map(|row| *row.iter().max().unwrap())
+        .max()
+        .unwrap();
+    labels.push(format!("segments: {}", number_of_segments));
+    labels
+}
+
+/**
+ * Renders the individuals into one grid image. Every cell shows the input image with the segment borders, the black
+ * and white border image and the segments image next to each other, labelled with the objective values and the
+ * number of segments. The cells are sorted from the best to the worst value of the configured objective.
+ */
+pub fn get_contact_sheet(
+    individuals: &[Individual],
+    contact_sheet_config: &ContactSheetConfig,
+    config: &Config,
+    global_data: &GlobalData
+) -> RgbImage {
+    let objective_index = global_data.objectives
+        .iter()
+        .position(|weighted_objective| {
+            weighted_objective.objective.name() == contact_sheet_config.sort_objective
+        })
+        .expect("The contact sheet sort objective is validated in initialize_objectives");
+    let mut sorted_individuals: Vec<&Individual> = individuals.iter().collect();
+    sorted_individuals.sort_by(|a, b| {
+        let ordering = a.get_objectives()[objective_index].partial_cmp(&b.get_objectives()[objective_index]).unwrap();
+        match global_data.objectives[objective_index].direction {
+            ObjectiveDirection::Minimize => ordering,
+            ObjectiveDirection::Maximize => ordering.reverse(),
+        }
+    });
+
+    let scale = contact_sheet_config.font_scale.max(1);
+    let spacing = SPACING * scale;
+    let line_height = get_line_height(scale);
+    let image_width = global_data.width as u32;
+    let image_height = global_data.height as u32;
+    // the objectives, the rank and the number of segments
+    let number_of_lines = (global_data.objectives.len() as u32) + 2;
+    let cell_width = 3 * image_width + 2 * spacing;
+    let cell_height = image_height + spacing + number_of_lines * line_height;
+
+    let number_of_columns = contact_sheet_config.number_of_columns.clamp(1, sorted_individuals.len().max(1)) as u32;
+    let number_of_rows = (sorted_individuals.len() as u32).div_ceil(number_of_columns);
+    let mut contact_sheet = RgbImage::from_pixel(
+        number_of_columns * (cell_width + spacing) + spacing,
+        number_of_rows.max(1) * (cell_height + spacing) + spacing,
+        Rgb([255, 255, 255])
+    );
+
+    for (rank, individual) in sorted_individuals.iter().enumerate() {
+        let x = spacing + ((rank as u32) % number_of_columns) * (cell_width + spacing);
+        let y = spacing + ((rank as u32) / number_of_columns) * (cell_height + spacing);
+        let images = [
+            individual.get_segment_border_image_inline(global_data),
+            individual.get_segment_border_image(global_data),
+            individual.get_segments_image(config, global_data),
+        ];
+        for (index, image) in images.iter().enumerate() {
+            let image_x = x + (index as u32) * (image_width + spacing);
+            overlay(&mut contact_sheet, image, image_x as i64, y as i64);
+        }
+        for (line, label) in get_labels(rank, individual, global_data).iter().enumerate() {
+            let label_y = y + image_height + spacing + (line as u32) * line_height;
+            draw_text(&mut contact_sheet, x, label_y, label, scale, Rgb([0, 0, 0]));
+        }
+    }
+    contact_sheet
+}
+
+/**
+ * Saves the contact sheet of the individuals to the output directory
+ */
+pub fn export_contact_sheet(
+    individuals: &[Individual],
+    contact_sheet_config: &ContactSheetConfig,
+    config: &Config,
+    global_data: &GlobalData
+) -> ImageResult<()> {
+    let contact_sheet = get_contact_sheet(individuals, contact_sheet_config, config, global_data);
+    let output_directory = get_output_directory(config);
+    let path = Path::new(&output_directory).join(
+        format!("contact_sheet_{}.png", rand::thread_rng().gen::<u32>())
+    );
+    contact_sheet.save(&path)?;
+    println!("Exported the contact sheet to {:?}", path);
+    Ok(())
+}
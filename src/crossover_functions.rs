@@ -1,5 +1,12 @@
-use crate::{ config::Config, global_data::GlobalData, individual::Genome, population::Population };
-use rand::Rng;
+use std::collections::HashSet;
+
+use crate::{
+    config::{ Config, FunctionConfig },
+    global_data::GlobalData,
+    individual::{ build_spanning_tree, Connection, Genome, Individual },
+    population::Population,
+};
+use rand::{ seq::SliceRandom, Rng };
 
 pub fn one_point_crossover(genome1: &Genome, genome2: &Genome) -> (Genome, Genome) {
     assert_eq!(genome1.len(), genome2.len());
@@ -44,13 +51,13 @@ pub fn n_point_crossover(
     (child1, child2)
 }
 
-pub fn uniform_crossover(genome1: &Genome, genome2: &Genome) -> (Genome, Genome) {
+pub fn uniform_crossover(genome1: &Genome, genome2: &Genome, swap_probability: f64) -> (Genome, Genome) {
     assert_eq!(genome1.len(), genome2.len());
     let mut rng = rand::thread_rng();
     let mut child1 = genome1.clone();
     let mut child2 = genome2.clone();
     for i in 0..genome1.len() {
-        if rng.gen::<f64>() < 0.5 {
+        if rng.gen::<f64>() < swap_probability {
             child1[i] = genome2[i];
             child2[i] = genome1[i];
         }
@@ -58,23 +65,220 @@ pub fn uniform_crossover(genome1: &Genome, genome2: &Genome) -> (Genome, Genome)
     (child1, child2)
 }
 
-pub fn crossover(population: &mut Population, config: &Config) {
+/**
+ * Swaps the genes inside a random rectangle of the image
+ */
+pub fn block_crossover(
+    genome1: &Genome,
+    genome2: &Genome,
+    width: usize,
+    height: usize
+) -> (Genome, Genome) {
+    assert_eq!(genome1.len(), genome2.len());
     let mut rng = rand::thread_rng();
-    for crossover_config in config.crossovers.iter() {
-        // Calculate the number of crossovers which should happen for the specific config
-        let number_of_crossovers: u64 = (
-            ((config.population_size as f64) * crossover_config.probability.unwrap()) /
-            2.0
-        ).ceil() as u64;
-
-        for _ in 0..number_of_crossovers {
-            let individual_index_a: usize = rng.gen_range(0..config.population_size);
-            let mut individual_index_b: usize = rng.gen_range(0..config.population_size);
-
-            while individual_index_a == individual_index_b {
-                individual_index_b = rng.gen_range(0..config.population_size);
-            }
+    let top = rng.gen_range(0..height);
+    let bottom = rng.gen_range(top..height);
+    let left = rng.gen_range(0..width);
+    let right = rng.gen_range(left..width);
+
+    let mut child1 = genome1.clone();
+    let mut child2 = genome2.clone();
+    for row in top..=bottom {
+        for column in left..=right {
+            let index = row * width + column;
+            child1[index] = genome2[index];
+            child2[index] = genome1[index];
+        }
+    }
+    (child1, child2)
+}
+
+/**
+ * Rebuilds the genome of the marked pixels, so every 4-connected area of marked pixels with the same label
+ * becomes a tree of connections. The genome of all other pixels is not touched.
+ */
+fn rebuild_marked_segments(
+    genome: &mut Genome,
+    label_map: &[usize],
+    is_marked: &[bool],
+    width: usize,
+    height: usize
+) {
+    let mut assigned = vec![false; width * height];
+    for index in 0..width * height {
+        if !is_marked[index] || assigned[index] {
+            continue;
+        }
+        let label = label_map[index];
+        let tree = build_spanning_tree(genome, index, Connection::None, width, height, |pixel| {
+            is_marked[pixel] && label_map[pixel] == label
+        });
+        for pixel in tree {
+            assigned[pixel] = true;
+        }
+    }
+}
+
+fn get_flat_cluster_map(genome: &Genome, width: usize, height: usize) -> Vec<usize> {
+    let individual = Individual::new_with_genome(genome);
+    individual
+        .get_cluster_map(width as i64, height as i64)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/**
+ * Copies random whole segments of the donor into the receiver. The segments of the receiver which lose pixels are
+ * rebuilt from their remaining pixels, so the copied segments stay exactly as they are in the donor.
+ */
+fn copy_segments(
+    receiver: &Genome,
+    donor: &Genome,
+    receiver_cluster_map: &[usize],
+    donor_cluster_map: &[usize],
+    number_of_segments: usize,
+    width: usize,
+    height: usize
+) -> Genome {
+    let mut rng = rand::thread_rng();
+    let number_of_donor_segments = *donor_cluster_map.iter().max().unwrap();
+    let copied_segments: HashSet<usize> = (1..=number_of_donor_segments)
+        .collect::<Vec<usize>>()
+        .choose_multiple(&mut rng, number_of_segments)
+        .cloned()
+        .collect();
 
+    let mut child = receiver.clone();
+    let mut is_copied = vec![false; width * height];
+    let mut touched_segments: HashSet<usize> = HashSet::new();
+    for index in 0..width * height {
+        if copied_segments.contains(&donor_cluster_map[index]) {
+            is_copied[index] = true;
+            child[index] = donor[index];
+            touched_segments.insert(receiver_cluster_map[index]);
+        }
+    }
+
+    let is_marked: Vec<bool> = (0..width * height)
+        .map(|index| !is_copied[index] && touched_segments.contains(&receiver_cluster_map[index]))
+        .collect();
+    rebuild_marked_segments(&mut child, receiver_cluster_map, &is_marked, width, height);
+    child
+}
+
+/**
+ * Every child receives random whole segments of the other parent
+ */
+pub fn segment_preserving_crossover(
+    genome1: &Genome,
+    genome2: &Genome,
+    number_of_segments: usize,
+    width: usize,
+    height: usize
+) -> (Genome, Genome) {
+    assert_eq!(genome1.len(), genome2.len());
+    let cluster_map1 = get_flat_cluster_map(genome1, width, height);
+    let cluster_map2 = get_flat_cluster_map(genome2, width, height);
+    (
+        copy_segments(genome1, genome2, &cluster_map1, &cluster_map2, number_of_segments, width, height),
+        copy_segments(genome2, genome1, &cluster_map2, &cluster_map1, number_of_segments, width, height),
+    )
+}
+
+/**
+ * Keeps all boundaries of the main parent and adds the boundaries of the other parent inside a random half of the
+ * segments of the main parent
+ */
+fn inherit_boundaries(
+    main: &Genome,
+    main_cluster_map: &[usize],
+    other_cluster_map: &[usize],
+    width: usize,
+    height: usize
+) -> Genome {
+    let mut rng = rand::thread_rng();
+    let number_of_segments = *main_cluster_map.iter().max().unwrap();
+    let is_split: Vec<bool> = (0..=number_of_segments).map(|_| rng.gen::<f64>() < 0.5).collect();
+
+    // pixels of split segments are labeled by the pair of both cluster ids
+    let number_of_other_segments = *other_cluster_map.iter().max().unwrap() + 1;
+    let label_map: Vec<usize> = (0..width * height)
+        .map(|index| main_cluster_map[index] * number_of_other_segments + other_cluster_map[index])
+        .collect();
+    let is_marked: Vec<bool> = (0..width * height).map(|index| is_split[main_cluster_map[index]]).collect();
+
+    let mut child = main.clone();
+    rebuild_marked_segments(&mut child, &label_map, &is_marked, width, height);
+    child
+}
+
+/**
+ * Every child keeps the segment boundaries of one parent and inherits the boundaries of the other parent
+ * inside some of its segments
+ */
+pub fn boundary_crossover(
+    genome1: &Genome,
+    genome2: &Genome,
+    width: usize,
+    height: usize
+) -> (Genome, Genome) {
+    assert_eq!(genome1.len(), genome2.len());
+    let cluster_map1 = get_flat_cluster_map(genome1, width, height);
+    let cluster_map2 = get_flat_cluster_map(genome2, width, height);
+    (
+        inherit_boundaries(genome1, &cluster_map1, &cluster_map2, width, height),
+        inherit_boundaries(genome2, &cluster_map2, &cluster_map1, width, height),
+    )
+}
+
+/**
+ * Returns the pairs of individuals which are crossed, depending on the probability mode of the crossover:
+ * "count" crosses ceil(population size * probability / 2) random pairs and "per_individual" pairs up the shuffled
+ * population and crosses every pair with the probability. The crossovers always work on pairs, so they can't be
+ * applied per gene.
+ */
+fn get_crossover_pairs(crossover_config: &FunctionConfig, population_size: usize) -> Vec<(usize, usize)> {
+    let mut rng = rand::thread_rng();
+    let probability = crossover_config.probability.unwrap();
+    let mut shuffled_indices: Vec<usize> = (0..population_size).collect();
+    shuffled_indices.shuffle(&mut rng);
+    let shuffled_pairs = shuffled_indices.chunks_exact(2).map(|pair| (pair[0], pair[1]));
+
+    match crossover_config.probability_mode.as_deref().unwrap_or("count") {
+        "count" => {
+            let number_of_crossovers = (((population_size as f64) * probability) / 2.0).ceil() as usize;
+            (0..number_of_crossovers)
+                .map(|_| {
+                    let individual_index_a: usize = rng.gen_range(0..population_size);
+                    let mut individual_index_b: usize = rng.gen_range(0..population_size);
+                    while individual_index_a == individual_index_b {
+                        individual_index_b = rng.gen_range(0..population_size);
+                    }
+                    (individual_index_a, individual_index_b)
+                })
+                .collect()
+        }
+        "per_individual" => shuffled_pairs.filter(|_| rng.gen::<f64>() < probability).collect(),
+        "per_gene" =>
+            panic!(
+                "The crossover {:?} can't be applied per gene. The uniform crossover has its own swap_probability",
+                crossover_config.name.as_str()
+            ),
+        _ =>
+            panic!(
+                "Didn't have an Implementation for probability mode: {:?}",
+                crossover_config.probability_mode
+            ),
+    }
+}
+
+pub fn crossover(population: &mut Population, config: &Config, global_data: &GlobalData) {
+    for (crossover_index, crossover_config) in config.crossovers.iter().enumerate() {
+        for (individual_index_a, individual_index_b) in get_crossover_pairs(
+            crossover_config,
+            population.len()
+        ) {
             let child_genomes: (Genome, Genome) = match crossover_config.name.as_str() {
                 "one_point" =>
                     one_point_crossover(
@@ -90,14 +294,37 @@ pub fn crossover(population: &mut Population, config: &Config) {
                 "uniform" =>
                     uniform_crossover(
                         &population[individual_index_a].genome,
-                        &population[individual_index_b].genome
+                        &population[individual_index_b].genome,
+                        crossover_config.swap_probability.unwrap_or(0.5)
+                    ),
+                "block" =>
+                    block_crossover(
+                        &population[individual_index_a].genome,
+                        &population[individual_index_b].genome,
+                        global_data.width,
+                        global_data.height
+                    ),
+                "segment_preserving" =>
+                    segment_preserving_crossover(
+                        &population[individual_index_a].genome,
+                        &population[individual_index_b].genome,
+                        crossover_config.number_of_segments.unwrap_or(1),
+                        global_data.width,
+                        global_data.height
+                    ),
+                "boundary" =>
+                    boundary_crossover(
+                        &population[individual_index_a].genome,
+                        &population[individual_index_b].genome,
+                        global_data.width,
+                        global_data.height
                     ),
 
                 // Handle the rest of cases
                 _ =>
                     panic!(
                         "Didn't have an Implementation for crossover function: {:?}",
-                        config.parent_selection.name.as_str()
+                        crossover_config.name.as_str()
                     ),
             };
 
@@ -105,6 +332,125 @@ pub fn crossover(population: &mut Population, config: &Config) {
             population[individual_index_a].set_needs_update();
             population[individual_index_b].genome = child_genomes.1;
             population[individual_index_b].set_needs_update();
+            for individual_index in [individual_index_a, individual_index_b] {
+                if !population[individual_index].applied_crossovers.contains(&crossover_index) {
+                    population[individual_index].applied_crossovers.push(crossover_index);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::individual::get_genome_from_label_map;
+
+    const WIDTH: usize = 6;
+    const HEIGHT: usize = 4;
+
+    /**
+     * Three vertical stripes and four horizontal stripes, so no segment of one is a segment of the other
+     */
+    fn get_parents() -> (Genome, Genome) {
+        let vertical_stripes: Vec<Vec<usize>> = (0..HEIGHT)
+            .map(|_| (0..WIDTH).map(|column| column / 2).collect())
+            .collect();
+        let horizontal_stripes: Vec<Vec<usize>> = (0..HEIGHT).map(|row| vec![row; WIDTH]).collect();
+        (get_genome_from_label_map(&vertical_stripes), get_genome_from_label_map(&horizontal_stripes))
+    }
+
+    /**
+     * The pixels of every segment
+     */
+    fn get_segments(cluster_map: &[usize]) -> BTreeSet<BTreeSet<usize>> {
+        let number_of_segments = *cluster_map.iter().max().unwrap();
+        (1..=number_of_segments)
+            .map(|segment| {
+                (0..cluster_map.len())
+                    .filter(|index| cluster_map[*index] == segment)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn copy_segments_keeps_the_pixels_of_the_copied_segments() {
+        let (receiver, donor) = get_parents();
+        let receiver_cluster_map = get_flat_cluster_map(&receiver, WIDTH, HEIGHT);
+        let donor_cluster_map = get_flat_cluster_map(&donor, WIDTH, HEIGHT);
+        let donor_segments = get_segments(&donor_cluster_map);
+
+        for number_of_segments in 1..=3 {
+            for _ in 0..20 {
+                let child = copy_segments(
+                    &receiver,
+                    &donor,
+                    &receiver_cluster_map,
+                    &donor_cluster_map,
+                    number_of_segments,
+                    WIDTH,
+                    HEIGHT
+                );
+                let child_segments = get_segments(&get_flat_cluster_map(&child, WIDTH, HEIGHT));
+                // the horizontal stripes only exist in the child if they were copied
+                let copied_segments: Vec<&BTreeSet<usize>> = donor_segments
+                    .iter()
+                    .filter(|segment| child_segments.contains(*segment))
+                    .collect();
+                assert_eq!(copied_segments.len(), number_of_segments);
+
+                // the other segments of the child are the rest of the vertical stripes
+                let receiver_segments = get_segments(&receiver_cluster_map);
+                for child_segment in child_segments.iter().filter(|segment| !donor_segments.contains(*segment)) {
+                    assert!(
+                        receiver_segments.iter().any(|receiver_segment| child_segment.is_subset(receiver_segment)),
+                        "{:?}",
+                        child_segment
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inherit_boundaries_keeps_the_main_boundaries_and_splits_along_the_other_parent() {
+        let (main, other) = get_parents();
+        let main_cluster_map = get_flat_cluster_map(&main, WIDTH, HEIGHT);
+        let other_cluster_map = get_flat_cluster_map(&other, WIDTH, HEIGHT);
+        let other_segments = get_segments(&other_cluster_map);
+
+        for _ in 0..20 {
+            let child = inherit_boundaries(&main, &main_cluster_map, &other_cluster_map, WIDTH, HEIGHT);
+            let child_segments = get_segments(&get_flat_cluster_map(&child, WIDTH, HEIGHT));
+            for main_segment in get_segments(&main_cluster_map) {
+                // every main segment is either kept or split into its intersections with the other segments
+                let split_segments: BTreeSet<BTreeSet<usize>> = other_segments
+                    .iter()
+                    .map(|other_segment| main_segment.intersection(other_segment).copied().collect())
+                    .collect();
+                let is_kept = child_segments.contains(&main_segment);
+                let is_split = split_segments.is_subset(&child_segments);
+                assert!(is_kept != is_split, "{:?}", main_segment);
+            }
         }
     }
+
+    #[test]
+    fn uniform_crossover_swaps_genes_with_the_swap_probability() {
+        let (genome1, genome2) = get_parents();
+        assert_eq!(uniform_crossover(&genome1, &genome2, 0.0), (genome1.clone(), genome2.clone()));
+        assert_eq!(uniform_crossover(&genome1, &genome2, 1.0), (genome2.clone(), genome1.clone()));
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be applied per gene")]
+    fn crossovers_can_not_be_applied_per_gene() {
+        let crossover_config: FunctionConfig = serde_json
+            ::from_str(r#"{ "name": "uniform", "probability": 0.5, "probability_mode": "per_gene" }"#)
+            .unwrap();
+        get_crossover_pairs(&crossover_config, 4);
+    }
 }
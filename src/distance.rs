@@ -1,37 +1,229 @@
-use image::{ Rgb, RgbImage };
+use image::RgbImage;
 
-pub type EuclideanDistanceMap = Vec<Vec<Vec<Vec<f64>>>>;
+pub type ColorMap = Vec<Vec<(f64, f64, f64)>>;
 
-pub fn euclidean_distance(pixel_a: &Rgb<u8>, pixel_b: &Rgb<u8>) -> f64 {
-    let red_difference = pixel_a[0].abs_diff(pixel_b[0]) as u64;
-    let green_difference = pixel_a[1].abs_diff(pixel_b[1]) as u64;
-    let blue_difference = pixel_a[2].abs_diff(pixel_b[2]) as u64;
+pub type DistanceMap = Vec<Vec<Vec<Vec<f64>>>>;
 
-    return (
-        (red_difference.pow(2) + green_difference.pow(2) + blue_difference.pow(2)) as f64
-    ).sqrt();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistanceMetric {
+    Euclidean,
+    DeltaE76,
+    Ciede2000,
 }
 
-pub fn calculate_euclidean_distance_map_for_neighbors(
-    rgb_image: &RgbImage
-) -> EuclideanDistanceMap {
-    let height = rgb_image.height();
-    let width = rgb_image.width();
-    let mut euclidean_distance_map: EuclideanDistanceMap =
+pub fn get_distance_metric(name: &str, color_space: &str) -> DistanceMetric {
+    let metric = match name {
+        "euclidean" => DistanceMetric::Euclidean,
+        "delta_e_76" => DistanceMetric::DeltaE76,
+        "ciede2000" => DistanceMetric::Ciede2000,
+        _ => panic!("Didn't have an Implementation for distance metric: {:?}", name),
+    };
+    if metric != DistanceMetric::Euclidean && color_space != "lab" {
+        panic!("The distance metric {:?} requires the color space \"lab\".", name);
+    }
+    metric
+}
+
+fn srgb_to_linear(channel: u8) -> f64 {
+    let value = (channel as f64) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/**
+ * Converts a sRGB pixel to CIELAB using the D65 white point
+ */
+fn rgb_to_lab(pixel: &image::Rgb<u8>) -> (f64, f64, f64) {
+    let red = srgb_to_linear(pixel[0]);
+    let green = srgb_to_linear(pixel[1]);
+    let blue = srgb_to_linear(pixel[2]);
+
+    let x = (0.4124564 * red + 0.3575761 * green + 0.1804375 * blue) / 0.95047;
+    let y = 0.2126729 * red + 0.7151522 * green + 0.072175 * blue;
+    let z = (0.0193339 * red + 0.119192 * green + 0.9503041 * blue) / 1.08883;
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 { t.cbrt() } else { ((24389.0 / 27.0) * t + 16.0) / 116.0 }
+    };
+    let (f_x, f_y, f_z) = (f(x), f(y), f(z));
+
+    (116.0 * f_y - 16.0, 500.0 * (f_x - f_y), 200.0 * (f_y - f_z))
+}
+
+/**
+ * Converts a RGB pixel to HSV and projects it onto the HSV cone, so that the hue wraps around correctly
+ * and euclidean distances and centroids stay meaningful. All axes are scaled to the range of RGB values.
+ */
+fn rgb_to_hsv_cone(pixel: &image::Rgb<u8>) -> (f64, f64, f64) {
+    let red = (pixel[0] as f64) / 255.0;
+    let green = (pixel[1] as f64) / 255.0;
+    let blue = (pixel[2] as f64) / 255.0;
+
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    let value = max;
+
+    let radius = saturation * value * 255.0;
+    (radius * hue.to_radians().cos(), radius * hue.to_radians().sin(), value * 255.0)
+}
+
+/**
+ * Range of a color channel compared to rgb, to scale thresholds which were tuned for rgb. The hsv cone is already
+ * scaled to 0 to 255, lab has a lightness of 0 to 100.
+ */
+pub fn get_color_space_scale(color_space: &str) -> f64 {
+    match color_space {
+        "rgb" | "hsv" => 1.0,
+        "lab" => 100.0 / 255.0,
+        _ => panic!("Didn't have an Implementation for color space: {:?}", color_space),
+    }
+}
+
+pub fn convert_image_to_color_space(rgb_image: &RgbImage, color_space: &str) -> ColorMap {
+    let mut color_map: ColorMap =
+        vec![
+            vec![(0.0, 0.0, 0.0); rgb_image.width() as usize];
+            rgb_image.height() as usize
+        ];
+
+    for (column, row, pixel) in rgb_image.enumerate_pixels() {
+        color_map[row as usize][column as usize] = match color_space {
+            "rgb" => (pixel[0] as f64, pixel[1] as f64, pixel[2] as f64),
+            "lab" => rgb_to_lab(pixel),
+            "hsv" => rgb_to_hsv_cone(pixel),
+            _ => panic!("Didn't have an Implementation for color space: {:?}", color_space),
+        };
+    }
+    color_map
+}
+
+pub fn euclidean_distance(pixel_a: &(f64, f64, f64), pixel_b: &(f64, f64, f64)) -> f64 {
+    (
+        (pixel_a.0 - pixel_b.0).powi(2) +
+        (pixel_a.1 - pixel_b.1).powi(2) +
+        (pixel_a.2 - pixel_b.2).powi(2)
+    ).sqrt()
+}
+
+/**
+ * CIEDE2000 color difference as described by Sharma et al. (2005). Both colors have to be in CIELAB.
+ */
+pub fn ciede2000_distance(lab_a: &(f64, f64, f64), lab_b: &(f64, f64, f64)) -> f64 {
+    let (l_1, a_1, b_1) = *lab_a;
+    let (l_2, a_2, b_2) = *lab_b;
+
+    let c_1 = (a_1.powi(2) + b_1.powi(2)).sqrt();
+    let c_2 = (a_2.powi(2) + b_2.powi(2)).sqrt();
+    let c_mean_pow_7 = ((c_1 + c_2) / 2.0).powi(7);
+    let g = 0.5 * (1.0 - (c_mean_pow_7 / (c_mean_pow_7 + (25.0f64).powi(7))).sqrt());
+
+    let a_1_prime = (1.0 + g) * a_1;
+    let a_2_prime = (1.0 + g) * a_2;
+    let c_1_prime = (a_1_prime.powi(2) + b_1.powi(2)).sqrt();
+    let c_2_prime = (a_2_prime.powi(2) + b_2.powi(2)).sqrt();
+
+    let hue_angle = |b: f64, a_prime: f64| {
+        if b == 0.0 && a_prime == 0.0 { 0.0 } else { b.atan2(a_prime).to_degrees().rem_euclid(360.0) }
+    };
+    let h_1_prime = hue_angle(b_1, a_1_prime);
+    let h_2_prime = hue_angle(b_2, a_2_prime);
+
+    let delta_l_prime = l_2 - l_1;
+    let delta_c_prime = c_2_prime - c_1_prime;
+    let delta_h_prime = if c_1_prime * c_2_prime == 0.0 {
+        0.0
+    } else if (h_2_prime - h_1_prime).abs() <= 180.0 {
+        h_2_prime - h_1_prime
+    } else if h_2_prime - h_1_prime > 180.0 {
+        h_2_prime - h_1_prime - 360.0
+    } else {
+        h_2_prime - h_1_prime + 360.0
+    };
+    let delta_big_h_prime =
+        2.0 * (c_1_prime * c_2_prime).sqrt() * (delta_h_prime / 2.0).to_radians().sin();
+
+    let l_mean_prime = (l_1 + l_2) / 2.0;
+    let c_mean_prime = (c_1_prime + c_2_prime) / 2.0;
+    let h_mean_prime = if c_1_prime * c_2_prime == 0.0 {
+        h_1_prime + h_2_prime
+    } else if (h_1_prime - h_2_prime).abs() <= 180.0 {
+        (h_1_prime + h_2_prime) / 2.0
+    } else if h_1_prime + h_2_prime < 360.0 {
+        (h_1_prime + h_2_prime + 360.0) / 2.0
+    } else {
+        (h_1_prime + h_2_prime - 360.0) / 2.0
+    };
+
+    let t =
+        1.0 - 0.17 * (h_mean_prime - 30.0).to_radians().cos() +
+        0.24 * (2.0 * h_mean_prime).to_radians().cos() +
+        0.32 * (3.0 * h_mean_prime + 6.0).to_radians().cos() -
+        0.2 * (4.0 * h_mean_prime - 63.0).to_radians().cos();
+    let delta_theta = 30.0 * (-((h_mean_prime - 275.0) / 25.0).powi(2)).exp();
+    let c_mean_prime_pow_7 = c_mean_prime.powi(7);
+    let r_c = 2.0 * (c_mean_prime_pow_7 / (c_mean_prime_pow_7 + (25.0f64).powi(7))).sqrt();
+    let s_l =
+        1.0 + (0.015 * (l_mean_prime - 50.0).powi(2)) / (20.0 + (l_mean_prime - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_mean_prime;
+    let s_h = 1.0 + 0.015 * c_mean_prime * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    (
+        (delta_l_prime / s_l).powi(2) +
+        (delta_c_prime / s_c).powi(2) +
+        (delta_big_h_prime / s_h).powi(2) +
+        r_t * (delta_c_prime / s_c) * (delta_big_h_prime / s_h)
+    ).sqrt()
+}
+
+pub fn color_distance(
+    pixel_a: &(f64, f64, f64),
+    pixel_b: &(f64, f64, f64),
+    metric: DistanceMetric
+) -> f64 {
+    match metric {
+        // Delta E 1976 is the euclidean distance in CIELAB
+        DistanceMetric::Euclidean | DistanceMetric::DeltaE76 => euclidean_distance(pixel_a, pixel_b),
+        DistanceMetric::Ciede2000 => ciede2000_distance(pixel_a, pixel_b),
+    }
+}
+
+pub fn calculate_distance_map_for_neighbors(
+    color_map: &ColorMap,
+    metric: DistanceMetric
+) -> DistanceMap {
+    let height = color_map.len();
+    let width = color_map[0].len();
+    let mut distance_map: DistanceMap =
         vec![
             vec![
                 vec![
                     vec![0.0; 7];
                     7
                 ];
-                width as usize
+                width
             ];
-            height as usize
+            height
         ];
 
-    for row in 0..height as usize {
-        for column in 0..width as usize {
-            let current_pixel = rgb_image.get_pixel(column as u32, row as u32);
+    for row in 0..height {
+        for column in 0..width {
+            let current_pixel = &color_map[row][column];
             for x_offset in -3 as i32..=3 {
                 for y_offset in -3 as i32..=3 {
                     // Dont calculate boundaries
@@ -44,19 +236,47 @@ pub fn calculate_euclidean_distance_map_for_neighbors(
                         continue;
                     }
 
-                    let neighbor_pixel = rgb_image.get_pixel(
-                        ((column as i32) + x_offset) as u32,
-                        ((row as i32) + y_offset) as u32
-                    );
+                    let neighbor_pixel =
+                        &color_map[((row as i32) + y_offset) as usize]
+                            [((column as i32) + x_offset) as usize];
 
-                    euclidean_distance_map[row][column][(y_offset + 3) as usize][
-                        (x_offset + 3) as usize
-                    ] = euclidean_distance(current_pixel, neighbor_pixel);
+                    distance_map[row][column][(y_offset + 3) as usize][(x_offset + 3) as usize] =
+                        color_distance(current_pixel, neighbor_pixel, metric);
                 }
             }
         }
     }
-    euclidean_distance_map
+    distance_map
+}
+
+// (row, column) offsets of the up, down, left and right neighbor
+pub const NEIGHBOR_OFFSETS: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/**
+ * Extracts the distances to the 4 direct neighbors of every pixel from the distance map into a flat vector,
+ * indexed by row * width + column and ordered like the neighbor offsets. Neighbors outside of the image are infinite.
+ */
+pub fn get_neighbor_distances(distance_map: &DistanceMap, width: usize, height: usize) -> Vec<[f64; 4]> {
+    let mut neighbor_distances = vec![[f64::INFINITY; 4]; width * height];
+    for row in 0..height {
+        for column in 0..width {
+            for (direction, (row_offset, column_offset)) in NEIGHBOR_OFFSETS.iter().enumerate() {
+                let neighbor_row = (row as i64) + row_offset;
+                let neighbor_column = (column as i64) + column_offset;
+                if
+                    neighbor_row < 0 ||
+                    neighbor_row >= (height as i64) ||
+                    neighbor_column < 0 ||
+                    neighbor_column >= (width as i64)
+                {
+                    continue;
+                }
+                neighbor_distances[row * width + column][direction] =
+                    distance_map[row][column][(3 + row_offset) as usize][(3 + column_offset) as usize];
+            }
+        }
+    }
+    neighbor_distances
 }
 
 /**
@@ -77,3 +297,43 @@ pub fn get_nearest_neighbor_value(x_offset: i32, y_offset: i32) -> i32 {
         _ => panic!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ciede2000_matches_the_reference_pairs_of_sharma() {
+        // pairs 1, 2, 7, 13, 15, 17, 25 and 34 of the test data of Sharma et al. (2005)
+        let pairs = [
+            ((50.0, 2.6772, -79.7751), (50.0, 0.0, -82.7485), 2.0425),
+            ((50.0, 3.1571, -77.2803), (50.0, 0.0, -82.7485), 2.8615),
+            ((50.0, 0.0, 0.0), (50.0, -1.0, 2.0), 2.3669),
+            ((50.0, 2.49, -0.001), (50.0, -2.49, 0.0009), 7.1792),
+            ((50.0, 2.49, -0.001), (50.0, -2.49, 0.0011), 7.2195),
+            ((50.0, 2.5, 0.0), (73.0, 25.0, -18.0), 27.1492),
+            ((60.2574, -34.0099, 36.2677), (60.4626, -34.1751, 39.4387), 1.2644),
+            ((22.7233, 20.0904, -46.694), (23.0331, 14.973, -42.5619), 2.0373),
+        ];
+        for (lab_a, lab_b, expected) in pairs.iter() {
+            assert!((ciede2000_distance(lab_a, lab_b) - expected).abs() < 1e-4, "{:?} {:?}", lab_a, lab_b);
+            assert!((ciede2000_distance(lab_b, lab_a) - expected).abs() < 1e-4, "{:?} {:?}", lab_b, lab_a);
+        }
+    }
+
+    #[test]
+    fn rgb_to_lab_matches_known_conversions() {
+        let conversions = [
+            ([0, 0, 0], (0.0, 0.0, 0.0)),
+            ([255, 255, 255], (100.0, 0.0, 0.0)),
+            ([255, 0, 0], (53.2408, 80.0925, 67.2032)),
+            ([0, 0, 255], (32.297, 79.1875, -107.8602)),
+        ];
+        for (rgb, expected) in conversions.iter() {
+            let lab = rgb_to_lab(&image::Rgb(*rgb));
+            assert!((lab.0 - expected.0).abs() < 0.01, "{:?} -> {:?}", rgb, lab);
+            assert!((lab.1 - expected.1).abs() < 0.01, "{:?} -> {:?}", rgb, lab);
+            assert!((lab.2 - expected.2).abs() < 0.01, "{:?} -> {:?}", rgb, lab);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::{ fs::read_dir, path::Path };
+
+use image::{ GrayImage, RgbImage };
+
+// the same settings as the python evaluator
+const BLACK_VALUE_THRESHOLD: u8 = 100;
+const COLOR_VALUE_SLACK_RANGE: i32 = 40;
+const PIXEL_RANGE_CHECK: i64 = 4;
+
+/**
+ * Share of the black (border) pixels of the first image which have a pixel of similar color
+ * within the pixel range in the second image
+ */
+fn compare_images(image_a: &GrayImage, image_b: &GrayImage) -> f64 {
+    let (width, height) = (image_a.width() as i64, image_a.height() as i64);
+    let mut number_of_black_pixels = 0;
+    let mut number_of_matches = 0;
+    for row in 0..height {
+        for column in 0..width {
+            let color = image_a.get_pixel(column as u32, row as u32)[0];
+            if color >= BLACK_VALUE_THRESHOLD {
+                continue;
+            }
+            number_of_black_pixels += 1;
+
+            'search: for neighbor_row in row - PIXEL_RANGE_CHECK..=row + PIXEL_RANGE_CHECK {
+                for neighbor_column in column - PIXEL_RANGE_CHECK..=column + PIXEL_RANGE_CHECK {
+                    if neighbor_row < 0 || neighbor_row >= height || neighbor_column < 0 || neighbor_column >= width {
+                        continue;
+                    }
+                    let neighbor_color = image_b.get_pixel(neighbor_column as u32, neighbor_row as u32)[0];
+                    if ((neighbor_color as i32) - (color as i32)).abs() < COLOR_VALUE_SLACK_RANGE {
+                        number_of_matches += 1;
+                        break 'search;
+                    }
+                }
+            }
+        }
+    }
+    (number_of_matches as f64) / (number_of_black_pixels.max(1) as f64)
+}
+
+/**
+ * Compares every result with all ground truth images in the directory like the python evaluator and prints the
+ * score of the best matching ground truth. Ground truth images with other dimensions are skipped.
+ * Returns the average score.
+ */
+pub fn evaluate_against_ground_truth(result_images: &[RgbImage], ground_truth_directory: &str) -> f64 {
+    let mut ground_truth_images: Vec<GrayImage> = vec![];
+    let mut entries: Vec<_> = read_dir(Path::new(ground_truth_directory))
+        .expect("Unable to read the ground truth directory")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        // the training images contain the ground truth with the prefix GT next to other images
+        if !file_name.starts_with("GT") {
+            continue;
+        }
+        match image::open(&path) {
+            Ok(ground_truth_image) => ground_truth_images.push(ground_truth_image.to_luma8()),
+            Err(error) => println!("Skipping ground truth {:?}: {:?}", path, error),
+        }
+    }
+    if ground_truth_images.is_empty() {
+        println!("No ground truth images found in {}", ground_truth_directory);
+        return 0.0;
+    }
+
+    let mut total_score = 0.0;
+    for result_image in result_images.iter() {
+        let result_image = image::imageops::grayscale(result_image);
+        let mut highest_score: f64 = 0.0;
+        for ground_truth_image in ground_truth_images.iter() {
+            if ground_truth_image.dimensions() != result_image.dimensions() {
+                continue;
+            }
+            let score = compare_images(ground_truth_image, &result_image).min(
+                compare_images(&result_image, ground_truth_image)
+            );
+            highest_score = highest_score.max(score);
+        }
+        println!("Score: {:.2}%", highest_score * 100.0);
+        total_score += highest_score;
+    }
+    let average_score = total_score / (result_images.len().max(1) as f64);
+    println!("Total Average Score: {:.2}%", average_score * 100.0);
+    average_score
+}
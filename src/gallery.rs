@@ -0,0 +1,66 @@
+use std::{ fs::{ create_dir_all, write }, path::Path };
+
+use image::ImageResult;
+use rand::Rng;
+
+use crate::{
+    config::{ get_output_directory, Config },
+    global_data::GlobalData,
+    individual::Individual,
+    utils::combine_images,
+};
+
+/**
+ * Headless alternative to showing the results in a window. Saves the input image with the segment borders, the black
+ * and white border image and the segments image of every individual next to each other and writes an index.html which
+ * lists them with their objective values. Every call creates its own gallery directory, so the threads don't
+ * overwrite each other.
+ */
+pub fn export_gallery(
+    individuals: &[Individual],
+    config: &Config,
+    global_data: &GlobalData
+) -> ImageResult<()> {
+    let gallery_name = format!("gallery_{}", rand::thread_rng().gen::<u32>());
+    let output_directory = get_output_directory(config);
+    let path = Path::new(&output_directory).join(&gallery_name);
+    create_dir_all(&path)?;
+
+    let mut header = String::from("<th>Segmentation</th>");
+    for weighted_objective in global_data.objectives.iter() {
+        header += &format!("<th>{}</th>", weighted_objective.objective.name());
+    }
+    header += "<th>Weighted Fitness</th>";
+
+    let mut rows = String::new();
+    for (index, individual) in individuals.iter().enumerate() {
+        let file_name = format!("individual_{}.png", index);
+        let combined = combine_images(
+            &individual.get_segment_border_image_inline(global_data),
+            &combine_images(
+                &individual.get_segment_border_image(global_data),
+                &individual.get_segments_image(config, global_data)
+            )
+        );
+        combined.save(path.join(&file_name))?;
+
+        rows += &format!("<tr><td><img src=\"{}\"></td>", file_name);
+        for value in individual.get_objectives().iter() {
+            rows += &format!("<td>{:.4}</td>", value);
+        }
+        rows += &format!("<td>{:.4}</td></tr>\n", individual.get_fitness());
+    }
+
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>table {{ border-collapse: collapse; }} td, th {{ border: 1px solid #ccc; padding: 4px; }}</style>\n\
+         </head>\n<body>\n<h1>{}</h1>\n<table>\n<tr>{}</tr>\n{}</table>\n</body>\n</html>\n",
+        config.problem_instance,
+        config.problem_instance,
+        header,
+        rows
+    );
+    write(path.join("index.html"), html)?;
+    println!("Exported the gallery to {:?}", path.join("index.html"));
+    Ok(())
+}
@@ -1,9 +1,20 @@
 use std::io::{ self, Write };
 
+use image::{ ImageResult, RgbImage };
+
+use crate::animation::{ export_animation, get_animation_frame };
+use crate::contact_sheet::export_contact_sheet;
 use crate::crossover_functions::crossover;
+use crate::evaluation::evaluate_against_ground_truth;
+use crate::gallery::export_gallery;
 
 use crate::global_data::GlobalData;
-use crate::individual::Individual;
+use crate::individual::{ Individual, MutationParameters };
+use crate::local_search::refine_non_dominated_individuals;
+use crate::objectives::ObjectiveDirection;
+use crate::operator_selection::{ get_operator_rewards, AdaptiveOperatorSelection };
+use crate::post_processing::merge_small_segments;
+#[cfg(feature = "gui")]
 use crate::utils::show_with_data;
 
 use crate::mutation_functions::mutate;
@@ -13,71 +24,84 @@ use crate::population::{ initialize_population, non_dominated_sort, save_individ
 
 fn log_population_statistics(
     population: &Population,
-    current_population_ranked: &Vec<Vec<Individual>>,
-    iteration: usize
+    current_population_ranked: &[Vec<Individual>],
+    _iteration: usize,
+    global_data: &GlobalData
 ) {
     // number of individuals in the skyline
     println!("Skyline: {:?}", current_population_ranked[0].len());
-    // statistics of the skyline
-    let mut min_edge_value_fitness = f64::MAX;
-    let mut max_edge_value_fitness = f64::MIN;
-    let mut avg_edge_value_fitness = 0.0;
-    let mut min_connectivity_fitness = f64::MAX;
-    let mut max_connectivity_fitness = f64::MIN;
-    let mut avg_connectivity_fitness = 0.0;
-    let mut min_overall_deviation_fitness = f64::MAX;
-    let mut max_overall_deviation_fitness = f64::MIN;
-    let mut avg_overall_deviation_fitness = 0.0;
-    let mut min_weighted_fitness = f64::MAX;
-    let mut max_weighted_fitness = f64::MIN;
-    let mut avg_weighted_fitness = 0.0;
+
+    // validity of the genomes over the whole population
+    let mut number_of_invalid_individuals = 0;
+    let mut number_of_out_of_image_connections = 0;
+    let mut number_of_cycles = 0;
+    for individual in population.iter() {
+        let report = individual.validate(global_data.width, global_data.height);
+        if !report.is_valid() {
+            number_of_invalid_individuals += 1;
+        }
+        number_of_out_of_image_connections += report.number_of_out_of_image_connections;
+        number_of_cycles += report.number_of_cycles;
+    }
+    // average of the self adapted mutation parameters
+    let parameters: Vec<MutationParameters> = population
+        .iter()
+        .filter_map(|individual| individual.mutation_parameters)
+        .collect();
+    if !parameters.is_empty() {
+        let number_of_parameters = parameters.len() as f64;
+        println!(
+            "Mutation parameters (avg): max_depth_percent_of_picture: {:.4} | radius: {:.2} | minimum_coverage_percentage: {:.4}",
+            parameters
+                .iter()
+                .map(|parameters| parameters.max_depth_percent_of_picture)
+                .sum::<f64>() / number_of_parameters,
+            parameters
+                .iter()
+                .map(|parameters| parameters.radius)
+                .sum::<f64>() / number_of_parameters,
+            parameters
+                .iter()
+                .map(|parameters| parameters.minimum_coverage_percentage)
+                .sum::<f64>() / number_of_parameters
+        );
+    }
+
+    println!(
+        "Invalid individuals: {} | Out of image connections: {} | Cycles: {}",
+        number_of_invalid_individuals,
+        number_of_out_of_image_connections,
+        number_of_cycles
+    );
+    // statistics of the skyline. One entry per objective and the weighted fitness as last entry
+    let number_of_objectives = global_data.objectives.len();
+    let mut min_values = vec![f64::MAX; number_of_objectives + 1];
+    let mut max_values = vec![f64::MIN; number_of_objectives + 1];
+    let mut avg_values = vec![0.0; number_of_objectives + 1];
     let mut file_output = String::new();
 
     for rank in 0..current_population_ranked.len() {
         for individual in current_population_ranked[rank].iter() {
-            let fitness = individual.get_objectives();
-            let edge_value_fitness = fitness.0;
-            let connectivity_fitness = fitness.1;
-            let overall_deviation_fitness = fitness.2;
-            let weighted_fitness = individual.get_fitness();
+            let mut values = individual.get_objectives().clone();
             file_output += &format!(
-                "({},{},{});",
-                edge_value_fitness,
-                connectivity_fitness,
-                overall_deviation_fitness
+                "({});",
+                values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
             );
+            values.push(individual.get_fitness());
 
-            if edge_value_fitness < min_edge_value_fitness {
-                min_edge_value_fitness = edge_value_fitness;
-            }
-            if edge_value_fitness > max_edge_value_fitness {
-                max_edge_value_fitness = edge_value_fitness;
-            }
-            avg_edge_value_fitness += edge_value_fitness;
-
-            if connectivity_fitness < min_connectivity_fitness {
-                min_connectivity_fitness = connectivity_fitness;
-            }
-            if connectivity_fitness > max_connectivity_fitness {
-                max_connectivity_fitness = connectivity_fitness;
-            }
-            avg_connectivity_fitness += connectivity_fitness;
-
-            if overall_deviation_fitness < min_overall_deviation_fitness {
-                min_overall_deviation_fitness = overall_deviation_fitness;
-            }
-            if overall_deviation_fitness > max_overall_deviation_fitness {
-                max_overall_deviation_fitness = overall_deviation_fitness;
-            }
-            avg_overall_deviation_fitness += overall_deviation_fitness;
-
-            if weighted_fitness < min_weighted_fitness {
-                min_weighted_fitness = weighted_fitness;
-            }
-            if weighted_fitness > max_weighted_fitness {
-                max_weighted_fitness = weighted_fitness;
+            for (index, value) in values.iter().enumerate() {
+                if *value < min_values[index] {
+                    min_values[index] = *value;
+                }
+                if *value > max_values[index] {
+                    max_values[index] = *value;
+                }
+                avg_values[index] += value;
             }
-            avg_weighted_fitness += weighted_fitness;
         }
         file_output += "\n";
     }
@@ -86,50 +110,134 @@ fn log_population_statistics(
     // let mut file = std::fs::File::create(format!("./logs/pareto_front_{}.txt", iteration)).unwrap();
     // file.write_all(file_output.as_bytes()).unwrap();
 
-    avg_connectivity_fitness /= population.len() as f64;
-    avg_overall_deviation_fitness /= population.len() as f64;
-    avg_edge_value_fitness /= population.len() as f64;
-    avg_weighted_fitness /= population.len() as f64;
+    for avg_value in avg_values.iter_mut() {
+        *avg_value /= population.len() as f64;
+    }
+
+    // the weighted fitness is always maximized
+    let mut directions: Vec<ObjectiveDirection> = global_data.objectives
+        .iter()
+        .map(|weighted_objective| weighted_objective.direction)
+        .collect();
+    directions.push(ObjectiveDirection::Maximize);
+    let mut names: Vec<&str> = global_data.objectives
+        .iter()
+        .map(|weighted_objective| weighted_objective.objective.name())
+        .collect();
+    names.push("weighted_fitness");
+
+    let format_row = |label: &str, values: Vec<f64>| {
+        let mut row = format!("{:<11}", label);
+        for (name, value) in names.iter().zip(values.iter()) {
+            row += &format!(" | {:<width$.2}", value, width = name.len());
+        }
+        row
+    };
 
     // print as table
+    let header: String = names
+        .iter()
+        .map(|name| format!(" | {}", name))
+        .collect();
+    println!("{:<11}{}", "Statistics:", header);
     println!(
-        "Statistics: | Edge Value Fitness | Connectivity Fitness | Overall Deviation Fitness | Weighted Fitness"
+        "{}",
+        format_row(
+            "Best:",
+            directions
+                .iter()
+                .enumerate()
+                .map(|(index, direction)| if *direction == ObjectiveDirection::Maximize {
+                    max_values[index]
+                } else {
+                    min_values[index]
+                })
+                .collect()
+        )
     );
+    println!("{}", format_row("Avg:", avg_values.clone()));
     println!(
-        "Best:       | {:<18.2} | {:<20.2} | {:<25.2} | {:<24.2}",
-        max_edge_value_fitness,
-        min_connectivity_fitness,
-        min_overall_deviation_fitness,
-        max_weighted_fitness
-    );
-    println!(
-        "Avg:        | {:<18.2} | {:<20.2} | {:<25.2} | {:<24.2}",
-        avg_edge_value_fitness,
-        avg_connectivity_fitness,
-        avg_overall_deviation_fitness,
-        avg_weighted_fitness
-    );
-    println!(
-        "Worst:      | {:<18.2} | {:<20.2} | {:<25.2} | {:<24.2}",
-        min_edge_value_fitness,
-        max_connectivity_fitness,
-        max_overall_deviation_fitness,
-        min_weighted_fitness
+        "{}",
+        format_row(
+            "Worst:",
+            directions
+                .iter()
+                .enumerate()
+                .map(|(index, direction)| if *direction == ObjectiveDirection::Maximize {
+                    min_values[index]
+                } else {
+                    max_values[index]
+                })
+                .collect()
+        )
     );
     println!("");
 }
 
-pub fn run_genetic_algorithm_instance(config: &Config, global_data: &GlobalData) {
+/**
+ * Repairs the invalid connections of all individuals if a repair method is configured
+ */
+fn repair_population(population: &mut Population, config: &Config, global_data: &GlobalData) {
+    if let Some(repair_method) = &config.repair_method {
+        for individual in population.iter_mut() {
+            individual.repair(repair_method, global_data);
+        }
+    }
+}
+
+/**
+ * Runs the genetic algorithm and returns the final population. If an initial population is given, it replaces the
+ * configured initialization, e.g. with the upsampled population of a coarser pyramid level.
+ */
+pub fn run_genetic_algorithm_instance(
+    config: &Config,
+    global_data: &GlobalData,
+    initial_population: Option<Population>
+) -> Population {
     println!("Starting Genetic Algorithm Instance");
     print!("Initializing Population...");
-    let mut population: Population = initialize_population(config, global_data);
+    let mut population: Population = match initial_population {
+        Some(initial_population) => initial_population,
+        None => initialize_population(config, global_data),
+    };
 
     print!("DONE\nInitial Population Statistics: \n");
 
+    let mut crossover_selection = config.adaptive_operator_selection
+        .as_ref()
+        .map(|selection_config| AdaptiveOperatorSelection::new(&config.crossovers, selection_config));
+    let mut mutation_selection = config.adaptive_operator_selection
+        .as_ref()
+        .map(|selection_config| AdaptiveOperatorSelection::new(&config.mutations, selection_config));
+
+    let mut animation_frames = vec![];
+
     for generation in 0..config.number_of_generations {
+        // the operator probabilities of this generation
+        let mut generation_config = config.clone();
+        if let Some(selection) = &crossover_selection {
+            selection.apply_probabilities(&mut generation_config.crossovers);
+            selection.log_probabilities("Crossover");
+        }
+        if let Some(selection) = &mutation_selection {
+            selection.apply_probabilities(&mut generation_config.mutations);
+            selection.log_probabilities("Mutation");
+        }
+
         let current_population_ranked = non_dominated_sort(&population);
 
-        log_population_statistics(&population, &current_population_ranked, generation);
+        log_population_statistics(
+            &population,
+            &current_population_ranked,
+            generation,
+            global_data
+        );
+
+        if let Some(animation_config) = &config.animation {
+            if animation_config.interval > 0 && generation % animation_config.interval == 0 {
+                animation_frames.push(get_animation_frame(&current_population_ranked[0], generation, global_data));
+            }
+        }
 
         println!("Calculating Generation: {:?}", generation);
 
@@ -140,11 +248,13 @@ pub fn run_genetic_algorithm_instance(config: &Config, global_data: &GlobalData)
         print!("CROSS|");
         io::stdout().flush().unwrap();
         let mut children = parents.clone();
-        crossover(&mut children, config);
+        crossover(&mut children, &generation_config, global_data);
+        repair_population(&mut children, config, global_data);
 
         print!("MUT|");
         io::stdout().flush().unwrap();
-        mutate(&mut children, config, global_data);
+        mutate(&mut children, &generation_config, global_data);
+        repair_population(&mut children, config, global_data);
 
         print!("EVAL|");
         io::stdout().flush().unwrap();
@@ -157,32 +267,120 @@ pub fn run_genetic_algorithm_instance(config: &Config, global_data: &GlobalData)
         println!("SURV_SEL");
         io::stdout().flush().unwrap();
         population = survivor_selection(&population, &children, config);
-    }
 
-    // export and show result images of the pareto front if we use multi objective
-    if config.export_pareto_front {
-        let pareto_fronts = non_dominated_sort(&population);
-        let _ = save_individuals_to_files(&pareto_fronts[0], config, global_data);
-        if config.show_images {
-            for individual in pareto_fronts[0].iter() {
-                show_with_data(
-                    &individual.get_segment_border_image_inline(global_data),
-                    individual,
-                    global_data
+        if let Some(selection) = crossover_selection.as_mut() {
+            selection.update(
+                &get_operator_rewards(
+                    config.crossovers.len(),
+                    children.iter().map(|child| &child.applied_crossovers).collect(),
+                    population.iter().map(|individual| &individual.applied_crossovers).collect()
+                )
+            );
+        }
+        if let Some(selection) = mutation_selection.as_mut() {
+            selection.update(
+                &get_operator_rewards(
+                    config.mutations.len(),
+                    children.iter().map(|child| &child.applied_mutations).collect(),
+                    population.iter().map(|individual| &individual.applied_mutations).collect()
+                )
+            );
+        }
+        for individual in population.iter_mut() {
+            individual.applied_crossovers.clear();
+            individual.applied_mutations.clear();
+        }
+
+        if let Some(memetic_config) = &config.memetic {
+            if memetic_config.interval > 0 && (generation + 1) % memetic_config.interval == 0 {
+                print!("LOCAL_SEARCH|");
+                io::stdout().flush().unwrap();
+                let number_of_evaluations = refine_non_dominated_individuals(
+                    &mut population,
+                    config,
+                    global_data,
+                    memetic_config.number_of_individuals,
+                    memetic_config.evaluation_budget
                 );
+                println!("{} evaluations", number_of_evaluations);
             }
         }
+    }
+
+    if let Some(animation_config) = &config.animation {
+        animation_frames.push(
+            get_animation_frame(&non_dominated_sort(&population)[0], config.number_of_generations, global_data)
+        );
+        if let Err(error) = export_animation(&animation_frames, animation_config, config) {
+            println!("Unable to export the animation: {:?}", error);
+        }
+    }
+
+    population
+}
+
+/**
+ * Compares the saved images with the ground truth if a ground truth directory is configured
+ */
+fn evaluate_saved_images(saved_images: ImageResult<Vec<RgbImage>>, config: &Config) {
+    match (saved_images, &config.ground_truth_directory) {
+        (Ok(saved_images), Some(ground_truth_directory)) => {
+            evaluate_against_ground_truth(&saved_images, ground_truth_directory);
+        }
+        (Err(error), _) => println!("Unable to save the results: {:?}", error),
+        _ => {}
+    }
+}
+
+/**
+ * Shows the individuals one after another in a window. Without the gui feature there is no window, so the gallery
+ * export has to be used instead.
+ */
+#[cfg(feature = "gui")]
+fn show_individuals(individuals: &[Individual], config: &Config, global_data: &GlobalData) {
+    for individual in individuals.iter() {
+        show_with_data(&individual.get_segment_border_image_inline(global_data), individual, config, global_data);
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+fn show_individuals(_individuals: &[Individual], _config: &Config, _global_data: &GlobalData) {
+    println!("show_images needs the gui feature, use export_gallery for headless runs");
+}
+
+/**
+ * Exports and shows the result images of the final population
+ */
+pub fn export_results(mut population: Population, config: &Config, global_data: &GlobalData) {
+    // export and show result images of the pareto front if we use multi objective
+    let mut individuals = if config.export_pareto_front {
+        non_dominated_sort(&population).swap_remove(0)
     } else {
         // sort by weighted fitness, export the best and show solution
-        population.sort_by(|a, b| b.get_fitness().partial_cmp(&a.get_fitness()).unwrap());
+        population.sort_by(|a, b| b.compare_weighted_fitness(a));
         println!("Best Individual Fitness: {:?}", population[0].get_fitness());
-        let _ = save_individuals_to_files(&vec![population[0].clone()], config, global_data);
-        if config.show_images {
-            show_with_data(
-                &population[0].get_segment_border_image_inline(global_data),
-                &population[0],
-                global_data
-            );
+        vec![population[0].clone()]
+    };
+    // post process once, so every export shows the same individuals
+    if config.post_processing_minimum_segment_size > 0 {
+        for individual in individuals.iter_mut() {
+            merge_small_segments(individual, config.post_processing_minimum_segment_size, config, global_data);
+        }
+    }
+
+    let saved_images = save_individuals_to_files(&individuals, config, global_data);
+    evaluate_saved_images(saved_images, config);
+    if config.export_gallery {
+        if let Err(error) = export_gallery(&individuals, config, global_data) {
+            println!("Unable to export the gallery: {:?}", error);
+        }
+    }
+    if let Some(contact_sheet_config) = &config.contact_sheet {
+        if let Err(error) = export_contact_sheet(&individuals, contact_sheet_config, config, global_data) {
+            println!("Unable to export the contact sheet: {:?}", error);
         }
     }
+    if config.show_images {
+        show_individuals(&individuals, config, global_data);
+    }
 }
@@ -1,19 +1,138 @@
+use std::path::Path;
+
 use image::{ GrayImage, RgbImage };
+use imageproc::edges::canny;
 
-use crate::distance::EuclideanDistanceMap;
+use crate::{
+    config::Config,
+    distance::{
+        calculate_distance_map_for_neighbors,
+        convert_image_to_color_space,
+        get_color_space_scale,
+        get_distance_metric,
+        ColorMap,
+        DistanceMap,
+        DistanceMetric,
+    },
+    objectives::{ initialize_objectives, WeightedObjective },
+    preprocessing::{ get_default_edge_steps, run_edge_pipeline, run_rgb_pipeline },
+    texture::{ calculate_texture_map, TextureMap },
+    weighted_sampler::WeightedSampler,
+};
 
 pub struct GlobalData<'a> {
     pub rgb_image: &'a RgbImage,
     pub edge_image: &'a GrayImage,
-    pub pixel_weights: &'a Vec<f64>,
 
-    pub euclidean_distance_map: &'a EuclideanDistanceMap,
+    // samples pixels proportional to their edge weights
+    pub pixel_sampler: &'a WeightedSampler,
+
+    // the image converted into the configured color space, indexed by [row][column]
+    pub color_map: &'a ColorMap,
+    // "rgb", "lab" or "hsv"
+    pub color_space: &'a str,
+    pub distance_metric: DistanceMetric,
+    pub distance_map: &'a DistanceMap,
+
+    // range of a color channel of the color space compared to rgb
+    pub color_space_scale: f64,
+
+    // only calculated if texture features are enabled in the config
+    pub texture_map: Option<&'a TextureMap>,
+
+    pub objectives: &'a Vec<WeightedObjective>,
 
     pub width: usize,
 
     pub height: usize,
 }
 
+/**
+ * Owns all data which is derived from one image, so the global data can be created for different images
+ * during one run, e.g. for every level of the image pyramid
+ */
+pub struct ImageData {
+    pub rgb_image: RgbImage,
+    pub edge_image: GrayImage,
+    pub pixel_sampler: WeightedSampler,
+    pub color_map: ColorMap,
+    pub color_space: String,
+    pub distance_metric: DistanceMetric,
+    pub distance_map: DistanceMap,
+    pub color_space_scale: f64,
+    pub texture_map: Option<TextureMap>,
+    pub objectives: Vec<WeightedObjective>,
+}
+
+impl ImageData {
+    /**
+     * Preprocesses the image and calculates everything the genetic algorithm needs.
+     * The intermediate images of the preprocessing are only exported if an export directory is given.
+     */
+    pub fn new(rgb_image: RgbImage, config: &Config, export_directory: Option<&Path>) -> ImageData {
+        // The edge image for the edge based objectives
+        let edge_image = canny(
+            &image::imageops::grayscale(&rgb_image),
+            config.canny_hysteresis_low,
+            config.canny_hysteresis_high
+        );
+
+        // Preprocess the image for the objectives and the mutation weights
+        let preprocessed_rgb_image = match &config.preprocessing {
+            Some(preprocessing_config) =>
+                run_rgb_pipeline(&rgb_image, &preprocessing_config.rgb_steps, export_directory),
+            None => rgb_image.clone(),
+        };
+        let edge_steps = config.preprocessing
+            .as_ref()
+            .and_then(|preprocessing_config| preprocessing_config.edge_steps.clone())
+            .unwrap_or_else(|| get_default_edge_steps(config));
+        let weight_image = run_edge_pipeline(
+            &image::imageops::grayscale(&preprocessed_rgb_image),
+            &edge_steps,
+            export_directory
+        );
+
+        // Convert the image into the color space all distances are calculated in
+        let distance_metric = get_distance_metric(&config.distance_metric, &config.color_space);
+        let color_map = convert_image_to_color_space(&preprocessed_rgb_image, &config.color_space);
+        let distance_map = calculate_distance_map_for_neighbors(&color_map, distance_metric);
+        let texture_map = config.texture
+            .as_ref()
+            .map(|texture_config| calculate_texture_map(&preprocessed_rgb_image, texture_config.window_radius));
+
+        ImageData {
+            rgb_image,
+            edge_image,
+            pixel_sampler: WeightedSampler::new(&generate_pixel_edge_weights(&weight_image)),
+            color_map,
+            color_space: config.color_space.clone(),
+            distance_metric,
+            distance_map,
+            color_space_scale: get_color_space_scale(&config.color_space),
+            texture_map,
+            objectives: initialize_objectives(config),
+        }
+    }
+
+    pub fn get_global_data(&self) -> GlobalData<'_> {
+        GlobalData {
+            rgb_image: &self.rgb_image,
+            edge_image: &self.edge_image,
+            pixel_sampler: &self.pixel_sampler,
+            color_map: &self.color_map,
+            color_space: &self.color_space,
+            distance_metric: self.distance_metric,
+            distance_map: &self.distance_map,
+            color_space_scale: self.color_space_scale,
+            texture_map: self.texture_map.as_ref(),
+            objectives: &self.objectives,
+            width: self.rgb_image.width() as usize,
+            height: self.rgb_image.height() as usize,
+        }
+    }
+}
+
 pub fn generate_pixel_edge_weights(image: &GrayImage) -> Vec<f64> {
     let (width, height) = image.dimensions();
     let mut weights = vec![0.0; (width * height) as usize];
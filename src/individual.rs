@@ -1,12 +1,14 @@
-use std::{ cmp::Ordering, collections::{ BinaryHeap, HashMap, HashSet }, vec };
-use image::{ ImageBuffer, Rgb, RgbImage, GrayImage };
-use imageproc::edges::canny;
+use std::{ cmp::Ordering, collections::BinaryHeap, vec };
+use image::{ ImageBuffer, Rgb, RgbImage };
 use rand::Rng;
 
 use crate::{
-    config::Config,
-    distance::{ get_nearest_neighbor_value, EuclideanDistanceMap },
+    config::{ Config, ConstraintConfig },
+    distance::{ get_neighbor_distances, DistanceMap },
     global_data::GlobalData,
+    objectives::ObjectiveDirection,
+    region_adjacency_graph::RegionAdjacencyGraph,
+    segment_coloring::{ get_graph_colors, get_mean_colors, get_palette_colors, get_random_hsv_colors },
 };
 
 // create a enum
@@ -20,15 +22,29 @@ pub enum Connection {
 }
 pub type Genome = Vec<Connection>;
 
+// an edge of the minimum spanning tree, the pixel is connected to its parent in the genome
+#[derive(Debug, Clone, Copy)]
+pub struct MstEdge {
+    pub pixel: usize,
+    pub parent: usize,
+    pub weight: f64,
+}
+
+/**
+ * Calculates a minimum spanning tree of the 4-neighborhood graph with Prim's algorithm, starting at a random pixel.
+ * Every pixel is connected to its parent in the tree and the start pixel has no connection.
+ * If a vector for the edges is given, all edges of the tree are pushed to it in the order they were added.
+ */
 pub fn get_mst_genome(
-    rgb_image: &image::RgbImage,
-    distance_map: &Vec<Vec<Vec<Vec<f64>>>>
+    distance_map: &DistanceMap,
+    width: usize,
+    height: usize,
+    mst_edges: Option<&mut Vec<MstEdge>>
 ) -> Genome {
-    #[derive(Debug)]
+    // The ordering is reversed, so the binary heap pops the element with the smallest distance first
     struct MSTelement {
-        row: usize,
-        column: usize,
-        direction: Connection,
+        pixel: usize,
+        parent: usize,
         distance: f64,
     }
 
@@ -42,97 +58,178 @@ pub fn get_mst_genome(
 
     impl PartialOrd for MSTelement {
         fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            self.distance.partial_cmp(&other.distance)
+            Some(self.cmp(other))
         }
     }
 
     impl Ord for MSTelement {
         fn cmp(&self, other: &Self) -> Ordering {
-            self.distance.partial_cmp(&other.distance).unwrap_or(Ordering::Equal)
+            other.distance.partial_cmp(&self.distance).unwrap_or(Ordering::Equal)
         }
     }
 
-    // init the genome with none
-    let mut genome =
-        vec![Connection::None; (rgb_image.width() * rgb_image.height()).try_into().unwrap()];
-    let mut unseen_pixels: HashSet<(usize, usize)> = HashSet::new();
-    for row in 0..rgb_image.height() {
-        for column in 0..rgb_image.width() {
-            unseen_pixels.insert((row as usize, column as usize));
-        }
-    }
-    let mut mst: BinaryHeap<MSTelement> = BinaryHeap::new();
+    // in the same order as the neighbor offsets of the compact distances
+    let directions = [Connection::Up, Connection::Down, Connection::Left, Connection::Right];
+    let number_of_pixels = width * height;
+    let neighbor_distances = get_neighbor_distances(distance_map, width, height);
+    let mut genome = vec![Connection::None; number_of_pixels];
+    let mut visited = vec![false; number_of_pixels];
+    let mut number_of_visited_pixels = 0;
+    let mut edges = mst_edges;
+    let mut mst: BinaryHeap<MSTelement> = BinaryHeap::with_capacity(number_of_pixels);
+
     // chose a random pixel to start
-    let start_row = rand::thread_rng().gen_range(0..rgb_image.height()) as usize;
-    let start_column = rand::thread_rng().gen_range(0..rgb_image.width()) as usize;
-    unseen_pixels.remove(&(start_row, start_column));
-    // add the start pixel to the mst
-    if start_row > 0 {
-        mst.push(MSTelement {
-            row: start_row - 1,
-            column: start_column,
-            direction: Connection::Down,
-            distance: distance_map[start_row][start_column][0][1],
-        });
-    }
-    if start_row < (rgb_image.height() as usize) - 1 {
-        mst.push(MSTelement {
-            row: start_row + 1,
-            column: start_column,
-            direction: Connection::Up,
-            distance: distance_map[start_row][start_column][2][1],
-        });
-    }
-    if start_column > 0 {
-        mst.push(MSTelement {
-            row: start_row,
-            column: start_column - 1,
-            direction: Connection::Right,
-            distance: distance_map[start_row][start_column][1][0],
-        });
+    let start = rand::thread_rng().gen_range(0..number_of_pixels);
+    let mut next = Some(MSTelement { pixel: start, parent: start, distance: 0.0 });
+
+    while let Some(mst_element) = next {
+        let pixel = mst_element.pixel;
+        if !visited[pixel] {
+            visited[pixel] = true;
+            number_of_visited_pixels += 1;
+            if pixel != mst_element.parent {
+                genome[pixel] = get_connection_to_neighbor(pixel, mst_element.parent, width);
+                if let Some(edges) = edges.as_mut() {
+                    edges.push(MstEdge {
+                        pixel,
+                        parent: mst_element.parent,
+                        weight: mst_element.distance,
+                    });
+                }
+            }
+            if number_of_visited_pixels == number_of_pixels {
+                break;
+            }
+            // Add the unvisited neighbors of the pixel to the heap
+            for (direction, distance) in neighbor_distances[pixel].iter().enumerate() {
+                let neighbor = match get_connected_pixel_index(pixel, directions[direction], width, height) {
+                    Some(neighbor) => neighbor,
+                    None => {
+                        continue;
+                    }
+                };
+                if !visited[neighbor] {
+                    mst.push(MSTelement { pixel: neighbor, parent: pixel, distance: *distance });
+                }
+            }
+        }
+        next = mst.pop();
     }
-    if start_column < (rgb_image.width() as usize) - 1 {
-        mst.push(MSTelement {
-            row: start_row,
-            column: start_column + 1,
-            direction: Connection::Left,
-            distance: distance_map[start_row][start_column][1][2],
-        });
+    genome
+}
+
+/**
+ * Returns the index of the pixel the connection points to or None if it points to nothing or outside of the image
+ */
+pub fn get_connected_pixel_index(
+    index: usize,
+    connection: Connection,
+    width: usize,
+    height: usize
+) -> Option<usize> {
+    let column = index % width;
+    let row = index / width;
+    match connection {
+        Connection::None => None,
+        Connection::Up if row > 0 => Some(index - width),
+        Connection::Down if row < height - 1 => Some(index + width),
+        Connection::Left if column > 0 => Some(index - 1),
+        Connection::Right if column < width - 1 => Some(index + 1),
+        _ => None,
     }
+}
 
-    while !unseen_pixels.is_empty() {
-        let mst_element = mst.pop().unwrap();
-        if unseen_pixels.remove(&(mst_element.row, mst_element.column)) {
-            genome[mst_element.row * (rgb_image.width() as usize) + mst_element.column] =
-                mst_element.direction;
-            // Add the neighbors of the pixel to the mst
-            for (row_adjustment, column_adjustment, dir) in &[
-                (0, -1, Connection::Right),
-                (0, 1, Connection::Left),
-                (-1, 0, Connection::Down),
-                (1, 0, Connection::Up),
-            ] {
-                let new_row = ((mst_element.row as isize) + row_adjustment) as usize;
-                let new_col = ((mst_element.column as isize) + column_adjustment) as usize;
-                if
-                    new_row < (rgb_image.height() as usize) &&
-                    new_col < (rgb_image.width() as usize) &&
-                    unseen_pixels.contains(&(new_row, new_col))
-                {
-                    mst.push(MSTelement {
-                        row: new_row,
-                        column: new_col,
-                        direction: *dir,
-                        distance: distance_map[mst_element.row][mst_element.column]
-                            [(1 - row_adjustment) as usize][(1 - column_adjustment) as usize],
-                    });
-                }
+/**
+ * Rebuilds the genome of all pixels which are reachable from the root through pixels accepted by is_member
+ * as a breadth first spanning tree. Every visited pixel points to the pixel it was reached from and the root
+ * gets the root connection. Returns the visited pixels in the order they were visited.
+ */
+pub fn build_spanning_tree(
+    genome: &mut Genome,
+    root: usize,
+    root_connection: Connection,
+    width: usize,
+    height: usize,
+    is_member: impl Fn(usize) -> bool
+) -> Vec<usize> {
+    let mut visited = vec![false; width * height];
+    let mut visited_pixels = vec![root];
+    visited[root] = true;
+    genome[root] = root_connection;
+
+    let mut current = 0;
+    while current < visited_pixels.len() {
+        let index = visited_pixels[current];
+        current += 1;
+        let column = index % width;
+        let row = index / width;
+
+        // the connection points from the neighbor back to the current pixel
+        for (row_adjustment, column_adjustment, direction) in [
+            (-1, 0, Connection::Down),
+            (1, 0, Connection::Up),
+            (0, -1, Connection::Right),
+            (0, 1, Connection::Left),
+        ] {
+            let new_row = ((row as isize) + row_adjustment) as usize;
+            let new_column = ((column as isize) + column_adjustment) as usize;
+            if new_row >= height || new_column >= width {
+                continue;
             }
+            let new_index = new_row * width + new_column;
+            if visited[new_index] || !is_member(new_index) {
+                continue;
+            }
+            visited[new_index] = true;
+            genome[new_index] = direction;
+            visited_pixels.push(new_index);
+        }
+    }
+    visited_pixels
+}
+
+/**
+ * Converts a label map into a genome where every 4-connected area with the same label is a tree of connections.
+ * Labels which are split into several areas become separate segments.
+ */
+pub fn get_genome_from_label_map(label_map: &[Vec<usize>]) -> Genome {
+    let height = label_map.len();
+    let width = label_map[0].len();
+    let mut genome = vec![Connection::None; width * height];
+    let mut assigned = vec![false; width * height];
+
+    for index in 0..width * height {
+        if assigned[index] {
+            continue;
+        }
+        let label = label_map[index / width][index % width];
+        let tree = build_spanning_tree(&mut genome, index, Connection::None, width, height, |pixel| {
+            label_map[pixel / width][pixel % width] == label
+        });
+        for pixel in tree {
+            assigned[pixel] = true;
         }
     }
     genome
 }
 
+/**
+ * Returns the connection which points from the pixel to its neighbor, if they are direct neighbors
+ */
+pub fn get_connection_to_neighbor(index: usize, neighbor_index: usize, width: usize) -> Connection {
+    if neighbor_index + width == index {
+        Connection::Up
+    } else if index + width == neighbor_index {
+        Connection::Down
+    } else if neighbor_index + 1 == index && !index.is_multiple_of(width) {
+        Connection::Left
+    } else if index + 1 == neighbor_index && !neighbor_index.is_multiple_of(width) {
+        Connection::Right
+    } else {
+        panic!("Pixel {} and {} are not neighbors", index, neighbor_index);
+    }
+}
+
 fn get_connected_pixels_for_pixel(
     genome: &Genome,
     index: i64,
@@ -245,16 +342,97 @@ fn is_border_pixel(
 
     false
 }
+/**
+ * Sums up how far the segmentation is away from satisfying the constraints. Each constraint is
+ * normalized by its bound so the different constraints are comparable
+ */
+fn get_constraint_violation(cluster_map: &[Vec<usize>], constraint_config: &ConstraintConfig) -> f64 {
+    // cluster ids are assigned consecutively starting with 1
+    let number_of_segments = cluster_map
+        .iter()
+        .map(|row| *row.iter().max().unwrap())
+        .max()
+        .unwrap();
+    let mut segment_sizes = vec![0; number_of_segments + 1];
+    for row in cluster_map.iter() {
+        for segment in row.iter() {
+            segment_sizes[*segment] += 1;
+        }
+    }
+
+    let mut constraint_violation = 0.0;
+    if let Some(minimum_number_of_segments) = constraint_config.minimum_number_of_segments {
+        if number_of_segments < minimum_number_of_segments {
+            constraint_violation +=
+                ((minimum_number_of_segments - number_of_segments) as f64) /
+                (minimum_number_of_segments as f64);
+        }
+    }
+    if let Some(maximum_number_of_segments) = constraint_config.maximum_number_of_segments {
+        if number_of_segments > maximum_number_of_segments {
+            constraint_violation +=
+                ((number_of_segments - maximum_number_of_segments) as f64) /
+                (maximum_number_of_segments.max(1) as f64);
+        }
+    }
+    if let Some(minimum_segment_size) = constraint_config.minimum_segment_size {
+        for segment_size in segment_sizes.iter().skip(1) {
+            if *segment_size < minimum_segment_size {
+                constraint_violation +=
+                    ((minimum_segment_size - segment_size) as f64) / (minimum_segment_size as f64);
+            }
+        }
+    }
+    constraint_violation
+}
+
+// mutation parameters which are carried and mutated by every individual if self adaptation is enabled
+#[derive(Debug, Clone, Copy)]
+pub struct MutationParameters {
+    pub max_depth_percent_of_picture: f64,
+    pub radius: f64,
+    pub minimum_coverage_percentage: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GenomeValidationReport {
+    // connections which point outside of the image, e.g. up in the first row
+    pub number_of_out_of_image_connections: usize,
+
+    // pixels without a connection
+    pub number_of_roots: usize,
+
+    // cycles of connections. Two pixels pointing at each other are a cycle of length 2
+    pub number_of_cycles: usize,
+    pub number_of_pixels_in_cycles: usize,
+    pub longest_cycle_length: usize,
+}
+
+impl GenomeValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.number_of_out_of_image_connections == 0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Individual {
     pub genome: Genome,
     needs_update: bool,
 
-    // penalty
-    edge_value_fitness: f64,
-    connectivity_fitness: f64,
-    overall_deviation_fitness: f64,
+    // values of the configured objectives in the order of the config
+    objectives: Vec<f64>,
+    objective_directions: Vec<ObjectiveDirection>,
     fitness: f64,
+
+    // 0 if the individual satisfies all constraints
+    constraint_violation: f64,
+
+    // indices of the crossovers and mutations in the config which created the individual in this generation
+    pub applied_crossovers: Vec<usize>,
+    pub applied_mutations: Vec<usize>,
+
+    // only set if self adaptation is enabled
+    pub mutation_parameters: Option<MutationParameters>,
 }
 
 impl Individual {
@@ -265,9 +443,12 @@ impl Individual {
             genome,
             needs_update: true,
             fitness: 0.0, // higher is better
-            edge_value_fitness: 0.0,
-            connectivity_fitness: 0.0,
-            overall_deviation_fitness: 0.0,
+            objectives: vec![],
+            objective_directions: vec![],
+            constraint_violation: 0.0,
+            applied_crossovers: vec![],
+            applied_mutations: vec![],
+            mutation_parameters: None,
         }
     }
 
@@ -276,9 +457,12 @@ impl Individual {
             genome: genome.clone(),
             needs_update: true,
             fitness: 0.0,
-            edge_value_fitness: 0.0,
-            connectivity_fitness: 0.0,
-            overall_deviation_fitness: 0.0,
+            objectives: vec![],
+            objective_directions: vec![],
+            constraint_violation: 0.0,
+            applied_crossovers: vec![],
+            applied_mutations: vec![],
+            mutation_parameters: None,
         }
     }
 
@@ -290,15 +474,128 @@ impl Individual {
         self.needs_update = true;
     }
 
-    pub fn open_image_as_rgb(image_path: &str) -> RgbImage {
-        let img = image::open(image_path).unwrap();
-        img.to_rgb8()
+    /**
+     * Checks the genome for connections which point outside of the image and collects statistics about the roots
+     * and cycles of the connection graph
+     */
+    pub fn validate(&self, width: usize, height: usize) -> GenomeValidationReport {
+        let mut report = GenomeValidationReport::default();
+        for (index, connection) in self.genome.iter().enumerate() {
+            if *connection == Connection::None {
+                report.number_of_roots += 1;
+            } else if get_connected_pixel_index(index, *connection, width, height).is_none() {
+                report.number_of_out_of_image_connections += 1;
+            }
+        }
+
+        // Every pixel has at most one outgoing connection, so following the connections from every pixel
+        // either ends in a root, in an already finished path or in a cycle on the current path
+        const UNVISITED: u8 = 0;
+        const ON_PATH: u8 = 1;
+        const FINISHED: u8 = 2;
+        let mut states = vec![UNVISITED; width * height];
+        let mut path: Vec<usize> = vec![];
+        for start in 0..width * height {
+            if states[start] != UNVISITED {
+                continue;
+            }
+            let mut current = Some(start);
+            while let Some(index) = current {
+                if states[index] == FINISHED {
+                    break;
+                }
+                if states[index] == ON_PATH {
+                    let cycle_length = path.len() - path.iter().position(|pixel| *pixel == index).unwrap();
+                    report.number_of_cycles += 1;
+                    report.number_of_pixels_in_cycles += cycle_length;
+                    report.longest_cycle_length = report.longest_cycle_length.max(cycle_length);
+                    break;
+                }
+                states[index] = ON_PATH;
+                path.push(index);
+                current = get_connected_pixel_index(index, self.genome[index], width, height);
+            }
+            for index in path.drain(..) {
+                states[index] = FINISHED;
+            }
+        }
+        report
     }
 
-    pub fn open_image_as_edge_map(image_path: &str, low: f32, high: f32) -> GrayImage {
-        let img = image::open(image_path).unwrap();
+    /**
+     * Repairs connections which point outside of the image. "none" removes the connection and "neighbor" connects
+     * the pixel to its most similar neighbor inside the image. Returns the number of repaired connections.
+     */
+    pub fn repair(&mut self, method: &str, global_data: &GlobalData) -> usize {
+        let width = global_data.width;
+        let height = global_data.height;
+        let mut number_of_repaired_connections = 0;
+        for index in 0..width * height {
+            let connection = self.genome[index];
+            if
+                connection == Connection::None ||
+                get_connected_pixel_index(index, connection, width, height).is_some()
+            {
+                continue;
+            }
+            self.genome[index] = match method {
+                "none" => Connection::None,
+                "neighbor" => {
+                    let row = index / width;
+                    let column = index % width;
+                    [
+                        (-1_i64, 0_i64, Connection::Up),
+                        (1, 0, Connection::Down),
+                        (0, -1, Connection::Left),
+                        (0, 1, Connection::Right),
+                    ]
+                        .iter()
+                        .filter(|(row_offset, column_offset, _)| {
+                            let new_row = (row as i64) + row_offset;
+                            let new_column = (column as i64) + column_offset;
+                            new_row >= 0 &&
+                                new_row < (height as i64) &&
+                                new_column >= 0 &&
+                                new_column < (width as i64)
+                        })
+                        .map(|(row_offset, column_offset, direction)| {
+                            let distance =
+                                global_data.distance_map[row][column][(3 + row_offset) as usize]
+                                    [(3 + column_offset) as usize];
+                            (*direction, distance)
+                        })
+                        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                        .map(|(direction, _)| direction)
+                        .unwrap_or(Connection::None)
+                }
+                _ => panic!("Didn't have an Implementation for repair method: {:?}", method),
+            };
+            number_of_repaired_connections += 1;
+        }
+        if number_of_repaired_connections > 0 {
+            self.needs_update = true;
+        }
+        number_of_repaired_connections
+    }
 
-        canny(&img.to_luma8(), low, high)
+    /**
+     * Opens any image format the image crate supports. Grayscale images are converted to rgb and transparent
+     * pixels are blended onto a white background.
+     */
+    pub fn open_image_as_rgb(image_path: &str) -> RgbImage {
+        let img = image::open(image_path).unwrap_or_else(|error| {
+            panic!("Unable to open the image {}: {:?}", image_path, error)
+        });
+        if !img.color().has_alpha() {
+            return img.to_rgb8();
+        }
+        let rgba_image = img.to_rgba8();
+        ImageBuffer::from_fn(rgba_image.width(), rgba_image.height(), |column, row| {
+            let pixel = rgba_image.get_pixel(column, row);
+            let alpha = (pixel[3] as f64) / 255.0;
+            let blend = |channel: u8| ((channel as f64) * alpha + 255.0 * (1.0 - alpha)).round() as u8;
+            Rgb([blend(pixel[0]), blend(pixel[1]), blend(pixel[2])])
+        })
     }
 
     fn init_random_genome(rgb_image: &image::RgbImage) -> Genome {
@@ -366,40 +663,47 @@ impl Individual {
         cluster_map
     }
 
-    pub fn get_segments_image(&self, global_data: &GlobalData) -> RgbImage {
-        let clustered_image = self.get_cluster_map(
-            global_data.width as i64,
-            global_data.height as i64
-        );
-        let mut image = ImageBuffer::from_pixel(
-            global_data.width as u32,
-            global_data.height as u32,
-            Rgb([0u8, 0u8, 0u8])
-        );
-
-        let colorpalett = vec![
-            (25, 200, 56),
-            (25, 200, 56),
-            (138, 42, 226),
-            (158, 72, 0),
-            (241, 76, 192),
-            (163, 163, 163),
-            (255, 195, 0),
-            (0, 214, 255),
-            (1, 62, 255),
-            (255, 123, 0)
-        ];
-
-        for row in 0..global_data.height {
-            for column in 0..global_data.width {
-                let pixel = image.get_pixel_mut(column as u32, row as u32);
-                let segment = clustered_image[row][column];
-                let color = colorpalett[segment % colorpalett.len()];
-                *pixel = image::Rgb([color.0, color.1, color.2]);
-            }
-        }
-
-        image
+    /**
+     * Colors every segment depending on the segment coloring of the config: "palette" cycles through a fixed palette,
+     * "mean_color" uses the mean color of the segment, "graph" gives adjacent segments different colors and
+     * "random_hsv" uses random colors from the segment coloring seed.
+     */
+    pub fn get_segments_image(&self, config: &Config, global_data: &GlobalData) -> RgbImage {
+        let region_adjacency_graph;
+        let clustered_image = if config.segment_coloring == "graph" {
+            region_adjacency_graph = Some(RegionAdjacencyGraph::from_individual(self, global_data));
+            region_adjacency_graph.as_ref().unwrap().cluster_map.clone()
+        } else {
+            region_adjacency_graph = None;
+            self.get_cluster_map(global_data.width as i64, global_data.height as i64)
+        };
+        // cluster ids are assigned consecutively starting with 1
+        let number_of_segments = clustered_image
+            .iter()
+            .map(|row| *row.iter().max().unwrap())
+            .max()
+            .unwrap();
+
+        let colors = match config.segment_coloring.as_str() {
+            "palette" => get_palette_colors(number_of_segments),
+            "mean_color" => get_mean_colors(&clustered_image, global_data.rgb_image, number_of_segments),
+            "graph" =>
+                get_graph_colors(
+                    region_adjacency_graph.as_ref().unwrap(),
+                    number_of_segments,
+                    config.segment_coloring_seed
+                ),
+            "random_hsv" => get_random_hsv_colors(number_of_segments, config.segment_coloring_seed),
+            _ =>
+                panic!(
+                    "Didn't have an Implementation for segment coloring: {:?}",
+                    config.segment_coloring.as_str()
+                ),
+        };
+
+        ImageBuffer::from_fn(global_data.width as u32, global_data.height as u32, |column, row| {
+            colors[clustered_image[row as usize][column as usize]]
+        })
     }
 
     pub fn update_objectives(&mut self, config: &Config, global_data: &GlobalData) {
@@ -409,111 +713,50 @@ impl Individual {
             global_data.height as i64
         );
 
-        // define result values for the three objectives
-        let mut edge_value_fitness: f64 = 0.0;
-        let mut connectivity_fitness: f64 = 0.0;
-        let mut overall_deviation_fitness: f64 = 0.0;
-
-        let rgb_image: &RgbImage = global_data.rgb_image;
-        let euclidean_distance_map: &EuclideanDistanceMap = global_data.euclidean_distance_map;
-
-        // Map which holds the sums for each color for all pixels in one cluster (key: cluster_id, (sum_r, sum_g, sum_b, number_of_pixels))
-        let mut overall_deviation_map: HashMap<usize, (f64, f64, f64, u32)> = HashMap::new();
-
-        // Calculate Objective: Edge Value & Connectivity
-        // Bot need to loop over all pixels, get the immediate neighbors and do something if they are not in the same segment
-        for row in 0..global_data.height {
-            for column in 0..global_data.width {
-                for x_offset in -1 as i32..=1 {
-                    for y_offset in -1 as i32..=1 {
-                        if
-                            (row == 0 && y_offset == -1) ||
-                            (row == global_data.height - 1 && y_offset == 1) ||
-                            (column == 0 && x_offset == -1) ||
-                            (column == global_data.width - 1 && x_offset == 1)
-                        {
-                            continue;
-                        }
-                        // check if the pixel is in the same cluster. If yes ignore it
-                        if
-                            clustered_image[row][column] ==
-                            clustered_image[((row as i32) + y_offset) as usize]
-                                [((column as i32) + x_offset) as usize]
-                        {
-                            continue;
-                        }
-                        // Edge Value := get the euclidian distance for all the neighbors which are not in the same segment
-                        edge_value_fitness +=
-                            euclidean_distance_map[row][column][(y_offset + 1) as usize]
-                                [(x_offset + 1) as usize];
-
-                        // Connectivity Value
-                        connectivity_fitness +=
-                            1.0 / (get_nearest_neighbor_value(x_offset, y_offset) as f64);
-                    }
-                }
+        self.objectives = Vec::with_capacity(global_data.objectives.len());
+        self.objective_directions = Vec::with_capacity(global_data.objectives.len());
+        self.fitness = 0.0;
+        for weighted_objective in global_data.objectives.iter() {
+            let value = weighted_objective.objective.evaluate(&clustered_image, config, global_data);
+            self.objectives.push(value);
+            self.objective_directions.push(weighted_objective.direction);
+            // maximized objectives increase the weighted fitness, minimized ones decrease it
+            self.fitness += weighted_objective.direction.sign() * weighted_objective.multiplier * value;
+        }
+        self.constraint_violation = match &config.constraints {
+            Some(constraint_config) => get_constraint_violation(&clustered_image, constraint_config),
+            None => 0.0,
+        };
+        self.needs_update = false;
+    }
 
-                // aggregate all the pixels colors for one image to calculate the centroid later
-                overall_deviation_map
-                    .entry(clustered_image[row][column])
-                    .and_modify(|(sum_red, sum_green, sum_blue, count)| {
-                        *sum_red += rgb_image.get_pixel(column as u32, row as u32)[0] as f64;
-                        *sum_green += rgb_image.get_pixel(column as u32, row as u32)[1] as f64;
-                        *sum_blue += rgb_image.get_pixel(column as u32, row as u32)[2] as f64;
-                        *count += 1;
-                    })
-                    .or_insert((
-                        rgb_image.get_pixel(column as u32, row as u32)[0] as f64,
-                        rgb_image.get_pixel(column as u32, row as u32)[1] as f64,
-                        rgb_image.get_pixel(column as u32, row as u32)[2] as f64,
-                        1,
-                    ));
-            }
+    pub fn get_constraint_violation(&self) -> f64 {
+        if self.needs_update {
+            panic!("Constraint violation needs to be updated before getting it");
         }
+        self.constraint_violation
+    }
 
-        // Calculate Objective: Overall Deviation
-
-        // calculate the centroid color for each cluster by consuming the aggregated map
-        let mut cluster_centroid_map: HashMap<usize, (f64, f64, f64)> = HashMap::new();
-        for (cluster_id, (sum_red, sum_green, sum_blue, count)) in overall_deviation_map {
-            cluster_centroid_map.insert(cluster_id, (
-                sum_red / (count as f64),
-                sum_green / (count as f64),
-                sum_blue / (count as f64),
-            ));
-        }
-
-        // formular states iterate of all pixels in all segments, which translates to loop over all pixels and get the segment for the pixel
-        // for every pixel get the distance to the centroid pixel and add it to the deviation
-
-        for row in 0..global_data.height as usize {
-            for column in 0..global_data.width as usize {
-                let current_pixel = rgb_image.get_pixel(column as u32, row as u32);
-                let centroid_pixel = cluster_centroid_map
-                    .get(&clustered_image[row][column])
-                    .unwrap();
-                overall_deviation_fitness += (
-                    ((current_pixel[0] as f64) - centroid_pixel.0).powi(2) +
-                    ((current_pixel[1] as f64) - centroid_pixel.1).powi(2) +
-                    ((current_pixel[2] as f64) - centroid_pixel.2).powi(2)
-                ).sqrt();
-            }
+    pub fn is_feasible(&self) -> bool {
+        self.get_constraint_violation() == 0.0
+    }
+
+    /**
+     * Compares the weighted fitness of two individuals, feasible individuals are always greater than infeasible ones
+     * and infeasible individuals are compared by their constraint violation
+     */
+    pub fn compare_weighted_fitness(&self, other: &Individual) -> Ordering {
+        if self.get_constraint_violation() != other.get_constraint_violation() {
+            return other.constraint_violation.partial_cmp(&self.constraint_violation).unwrap();
         }
-        self.edge_value_fitness = edge_value_fitness;
-        self.connectivity_fitness = connectivity_fitness;
-        self.overall_deviation_fitness = overall_deviation_fitness;
-        self.fitness =
-            edge_value_fitness * config.edge_value_multiplier +
-            -connectivity_fitness * config.connectivity_multiplier +
-            -overall_deviation_fitness * config.overall_deviation_multiplier;
-        self.needs_update = false;
+        self.get_fitness().partial_cmp(&other.get_fitness()).unwrap()
     }
 
-    pub fn get_objectives(&self) -> (f64, f64, f64) {
+    pub fn get_objectives(&self) -> &Vec<f64> {
         if self.needs_update {
             panic!("Objectives need to be updated before getting them");
         }
-        (self.edge_value_fitness, self.connectivity_fitness, self.overall_deviation_fitness)
+        &self.objectives
     }
 
     pub fn get_fitness(&self) -> f64 {
@@ -525,24 +768,31 @@ impl Individual {
 
     /**
      * Solution x dominates solution y, (x y), if:
-        – x is better than y in at least one objective,
-        – x is not worse than y in all other objectives
+     * – x is better than y in at least one objective,
+     * – x is not worse than y in all other objectives
+     * With constraints Deb's rule is applied first:
+     * – a feasible solution dominates every infeasible one
+     * – of two infeasible solutions the one with the smaller constraint violation dominates
      */
     pub fn dominates(&self, other: &Individual) -> bool {
         if self.needs_update || other.needs_update {
             panic!("Objectives need to be updated before comparing them");
         }
-        let better_in_atleast_one_objective =
-            self.edge_value_fitness > other.edge_value_fitness || // higher score is better
-            self.connectivity_fitness < other.connectivity_fitness || // lower score is better
-            self.overall_deviation_fitness < other.overall_deviation_fitness; // lower score is better
-
-        let worse_in_any_objective =
-            self.edge_value_fitness < other.edge_value_fitness ||
-            self.connectivity_fitness > other.connectivity_fitness ||
-            self.overall_deviation_fitness > other.overall_deviation_fitness;
+        if self.constraint_violation > 0.0 || other.constraint_violation > 0.0 {
+            return self.constraint_violation < other.constraint_violation;
+        }
+        let mut better_in_atleast_one_objective = false;
+        for (index, direction) in self.objective_directions.iter().enumerate() {
+            if direction.is_better(other.objectives[index], self.objectives[index]) {
+                // worse in any objective
+                return false;
+            }
+            if direction.is_better(self.objectives[index], other.objectives[index]) {
+                better_in_atleast_one_objective = true;
+            }
+        }
 
-        better_in_atleast_one_objective && !worse_in_any_objective
+        better_in_atleast_one_objective
     }
 
     pub fn get_segment_border_image(&self, global_data: &GlobalData) -> RgbImage {
@@ -626,3 +876,115 @@ impl Individual {
         border_map
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Checks that two pixels are in the same cluster exactly if they have the same expected area
+     */
+    fn assert_same_partition(cluster_map: &[Vec<usize>], expected_areas: &[Vec<usize>]) {
+        let width = expected_areas[0].len();
+        let number_of_pixels = expected_areas.len() * width;
+        for pixel in 0..number_of_pixels {
+            for other in 0..number_of_pixels {
+                let same_cluster =
+                    cluster_map[pixel / width][pixel % width] == cluster_map[other / width][other % width];
+                let same_area =
+                    expected_areas[pixel / width][pixel % width] == expected_areas[other / width][other % width];
+                assert_eq!(same_cluster, same_area, "pixels {} and {}", pixel, other);
+            }
+        }
+    }
+
+    #[test]
+    fn genome_from_label_map_preserves_the_partition() {
+        let label_map = vec![vec![1, 1, 2, 2], vec![1, 3, 3, 2], vec![4, 4, 4, 2]];
+        let individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+
+        assert!(individual.validate(4, 3).is_valid());
+        assert_same_partition(&individual.get_cluster_map(4, 3), &label_map);
+    }
+
+    #[test]
+    fn genome_from_label_map_splits_disconnected_labels() {
+        // the label 1 has two areas which are not 4-connected
+        let label_map = vec![vec![1, 1, 2, 2], vec![2, 2, 2, 2], vec![1, 1, 2, 3]];
+        let expected_areas = vec![vec![0, 0, 1, 1], vec![1, 1, 1, 1], vec![2, 2, 1, 3]];
+        let individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+
+        assert!(individual.validate(4, 3).is_valid());
+        assert_same_partition(&individual.get_cluster_map(4, 3), &expected_areas);
+    }
+
+    #[test]
+    fn genome_from_label_map_with_a_single_label_is_one_segment() {
+        let label_map = vec![vec![7; 5]; 4];
+        let individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+
+        assert_same_partition(&individual.get_cluster_map(5, 4), &label_map);
+        assert_eq!(
+            individual.genome
+                .iter()
+                .filter(|connection| **connection == Connection::None)
+                .count(),
+            1
+        );
+    }
+
+    /**
+     * 3x3 genome with a cycle of 4 pixels in the upper right and 4 connections which point outside of the image
+     */
+    fn get_invalid_genome() -> Genome {
+        vec![
+            Connection::Up,
+            Connection::Right,
+            Connection::Down,
+            Connection::Left,
+            Connection::Up,
+            Connection::Left,
+            Connection::Down,
+            Connection::Right,
+            Connection::Right
+        ]
+    }
+
+    #[test]
+    fn validate_reports_out_of_image_connections_and_cycles() {
+        let report = Individual::new_with_genome(&get_invalid_genome()).validate(3, 3);
+
+        assert!(!report.is_valid());
+        assert_eq!(report.number_of_out_of_image_connections, 4);
+        assert_eq!(report.number_of_roots, 0);
+        assert_eq!(report.number_of_cycles, 1);
+        assert_eq!(report.number_of_pixels_in_cycles, 4);
+        assert_eq!(report.longest_cycle_length, 4);
+    }
+
+    #[test]
+    fn repair_yields_a_valid_genome() {
+        let config = crate::config::get_test_config();
+        let image_data = crate::global_data::ImageData::new(
+            RgbImage::from_fn(3, 3, |column, row| Rgb([(column * 80) as u8, (row * 80) as u8, 100])),
+            &config,
+            None
+        );
+        let global_data = image_data.get_global_data();
+
+        for method in ["none", "neighbor"] {
+            let mut individual = Individual::new_with_genome(&get_invalid_genome());
+            assert_eq!(individual.repair(method, &global_data), 4, "{}", method);
+
+            let report = individual.validate(3, 3);
+            assert!(report.is_valid(), "{}", method);
+            assert_eq!(individual.repair(method, &global_data), 0, "{}", method);
+        }
+
+        // removing the connections keeps the cycle, the other connections are not changed
+        let mut individual = Individual::new_with_genome(&get_invalid_genome());
+        individual.repair("none", &global_data);
+        assert_eq!(individual.validate(3, 3).number_of_cycles, 1);
+        assert_eq!(individual.validate(3, 3).number_of_roots, 4);
+    }
+}
@@ -0,0 +1,135 @@
+use rand::seq::SliceRandom;
+
+use crate::{
+    config::Config,
+    distance::color_distance,
+    global_data::GlobalData,
+    individual::{ get_connection_to_neighbor, Individual },
+    population::Population,
+    region_adjacency_graph::RegionAdjacencyGraph,
+};
+
+/**
+ * Greedy hill climb on the segment boundaries. Boundary pixels whose color is closer to the mean color of a
+ * neighboring segment are re-pointed into that segment. A move is only kept if the new individual dominates the old
+ * one, so no objective gets worse. Stops if the evaluation budget is used up or a full pass over the boundary
+ * did not find an improvement. Returns the number of used evaluations.
+ */
+pub fn hill_climb(
+    individual: &mut Individual,
+    config: &Config,
+    global_data: &GlobalData,
+    evaluation_budget: usize
+) -> usize {
+    let width = global_data.width;
+    let height = global_data.height;
+    let mut number_of_evaluations = 0;
+    if individual.needs_update() {
+        individual.update_objectives(config, global_data);
+        number_of_evaluations += 1;
+    }
+
+    'passes: while number_of_evaluations < evaluation_budget {
+        let region_adjacency_graph = RegionAdjacencyGraph::from_individual(individual, global_data);
+
+        // (pixel, neighbor) of all moves which bring the pixel closer to the mean color of its segment
+        let mut candidate_moves: Vec<(usize, usize)> = vec![];
+        for index in 0..width * height {
+            let region = region_adjacency_graph.get_region_of_pixel(index);
+            let color = &global_data.color_map[index / width][index % width];
+            let mut best_distance = color_distance(
+                color,
+                &region_adjacency_graph.nodes.get(&region).unwrap().mean_color,
+                global_data.distance_metric
+            );
+            let mut best_neighbor = None;
+
+            let row = index / width;
+            let column = index % width;
+            for (row_adjustment, column_adjustment) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+            ] {
+                let new_row = ((row as isize) + row_adjustment) as usize;
+                let new_column = ((column as isize) + column_adjustment) as usize;
+                if new_row >= height || new_column >= width {
+                    continue;
+                }
+                let neighbor = new_row * width + new_column;
+                let neighbor_region = region_adjacency_graph.get_region_of_pixel(neighbor);
+                if neighbor_region == region {
+                    continue;
+                }
+                let distance = color_distance(
+                    color,
+                    &region_adjacency_graph.nodes.get(&neighbor_region).unwrap().mean_color,
+                    global_data.distance_metric
+                );
+                if distance < best_distance {
+                    best_distance = distance;
+                    best_neighbor = Some(neighbor);
+                }
+            }
+            if let Some(neighbor) = best_neighbor {
+                candidate_moves.push((index, neighbor));
+            }
+        }
+        candidate_moves.shuffle(&mut rand::thread_rng());
+
+        for (pixel, neighbor) in candidate_moves {
+            if number_of_evaluations >= evaluation_budget {
+                break 'passes;
+            }
+            let mut candidate = individual.clone();
+            candidate.genome[pixel] = get_connection_to_neighbor(pixel, neighbor, width);
+            candidate.set_needs_update();
+            candidate.update_objectives(config, global_data);
+            number_of_evaluations += 1;
+
+            if candidate.dominates(individual) {
+                *individual = candidate;
+                // the segments changed, so the candidates need to be recalculated
+                continue 'passes;
+            }
+        }
+        break;
+    }
+    number_of_evaluations
+}
+
+/**
+ * Applies the hill climb to random non-dominated individuals of the population.
+ * The evaluation budget is shared equally between them. Returns the number of used evaluations.
+ */
+pub fn refine_non_dominated_individuals(
+    population: &mut Population,
+    config: &Config,
+    global_data: &GlobalData,
+    number_of_individuals: usize,
+    evaluation_budget: usize
+) -> usize {
+    let mut non_dominated_indices: Vec<usize> = (0..population.len())
+        .filter(|index| {
+            !population.iter().any(|other| other.dominates(&population[*index]))
+        })
+        .collect();
+    non_dominated_indices.shuffle(&mut rand::thread_rng());
+    non_dominated_indices.truncate(number_of_individuals);
+    if non_dominated_indices.is_empty() {
+        return 0;
+    }
+
+    let budget_per_individual = evaluation_budget / non_dominated_indices.len();
+    let mut number_of_evaluations = 0;
+    for index in non_dominated_indices {
+        number_of_evaluations += hill_climb(
+            &mut population[index],
+            config,
+            global_data,
+            budget_per_individual
+        );
+    }
+    number_of_evaluations
+}
@@ -1,48 +1,120 @@
 use std::{ env, path::Path, thread };
 
-use config::{ initialize_config, Config };
-use image::Luma;
-use imageproc::filter::gaussian_blur_f32;
+use config::{ get_input_image_path, get_output_directory, initialize_config, Config };
+use image::imageops::{ resize, FilterType };
 
 use crate::{
-    distance::calculate_euclidean_distance_map_for_neighbors,
-    genetic_algorithm::run_genetic_algorithm_instance,
-    global_data::{ generate_pixel_edge_weights, GlobalData },
+    genetic_algorithm::{ export_results, run_genetic_algorithm_instance },
+    global_data::ImageData,
     individual::Individual,
-    population::clear_dir,
+    population::{ clear_dir, clear_exported_results, upsample_population, Population },
 };
 
+mod animation;
 mod config;
+mod contact_sheet;
 mod crossover_functions;
 mod distance;
+mod evaluation;
+mod gallery;
 mod genetic_algorithm;
 mod individual;
+mod local_search;
 mod mutation_functions;
+mod objectives;
+mod operator_selection;
 mod population;
+mod preprocessing;
+mod post_processing;
+mod region_adjacency_graph;
+mod segment_coloring;
 mod selection_functions;
+mod superpixels;
+mod texture;
 mod utils;
 mod global_data;
+mod weighted_sampler;
+
+const USAGE: &str =
+    "Usage: bio-ai-3 [config.json] [--config <path>] [--input <image>] [--ground-truth <directory>] [--output <directory>]";
 
 /**
- * We do create a lot of data here to use as global data later. It is all done at the start of the main function to ensure a global lifetime in the root closure
+ * Reads the config and applies the command line arguments on top of it. A single argument without a flag is the
+ * config path, like before the flags existed.
  */
-#[show_image::main]
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let config_path: &str;
-    if args.len() < 2 {
-        config_path = "./config.json";
-    } else {
-        config_path = &args[1];
+fn parse_arguments(args: &[String]) -> Config {
+    let mut config_path = "./config.json".to_string();
+    let mut input_image_path = None;
+    let mut ground_truth_directory = None;
+    let mut output_directory = None;
+
+    let mut index = 1;
+    while index < args.len() {
+        let flag = args[index].as_str();
+        if !flag.starts_with("--") {
+            config_path = args[index].clone();
+            index += 1;
+            continue;
+        }
+        let value = match args.get(index + 1) {
+            Some(value) => value.clone(),
+            None => panic!("Missing value for {}\n{}", flag, USAGE),
+        };
+        match flag {
+            "--config" => {
+                config_path = value;
+            }
+            "--input" => {
+                input_image_path = Some(value);
+            }
+            "--ground-truth" => {
+                ground_truth_directory = Some(value);
+            }
+            "--output" => {
+                output_directory = Some(value);
+            }
+            _ => panic!("Unknown argument {}\n{}", flag, USAGE),
+        }
+        index += 2;
     }
+
     // Load config
-    let config: Config = initialize_config(config_path);
+    let mut config: Config = initialize_config(&config_path);
+    if let Some(input_image_path) = input_image_path {
+        // the name of the image replaces the problem instance in the log directories
+        config.problem_instance = Path::new(&input_image_path)
+            .file_stem()
+            .map(|file_stem| file_stem.to_string_lossy().to_string())
+            .unwrap_or(config.problem_instance);
+        config.input_image_path = Some(input_image_path);
+    }
+    if ground_truth_directory.is_some() {
+        config.ground_truth_directory = ground_truth_directory;
+    }
+    if output_directory.is_some() {
+        config.output_directory = output_directory;
+    }
+    config
+}
+
+/**
+ * Every thread creates the image data for every pyramid level. The global data borrows from it for the run on that level
+ */
+#[cfg_attr(feature = "gui", show_image::main)]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let config = parse_arguments(&args);
     println!("{}", serde_json::to_string_pretty(&config).unwrap());
 
-    // Clean the export directory
-    let path_string = format!("./logs/result_segmentation/{}", config.problem_instance);
+    // Clean the export directory. A custom output directory may contain other files, so only the results of a
+    // previous run are removed there
+    let path_string = get_output_directory(&config);
     let path = Path::new(&path_string);
-    clear_dir(path);
+    if config.output_directory.is_some() {
+        clear_exported_results(path);
+    } else {
+        clear_dir(path);
+    }
 
     // Multithreading
     let mut handles = vec![];
@@ -51,48 +123,78 @@ fn main() {
 
         let handle = thread::spawn(move || {
             // Load the rgb image for the global data
-            let rgb_image = Individual::open_image_as_rgb(
-                &format!("./Project 3 training_images/{}/Test image.jpg", config.problem_instance)
-            );
-
-            // Load the edge image for the biased weights
-            let edge_image = Individual::open_image_as_edge_map(
-                &format!("./Project 3 training_images/{}/Test image.jpg", config.problem_instance),
-                config.canny_hysteresis_low,
-                config.canny_hysteresis_high
-            );
-            // Blur the image so we have a bit of a buffer around the edges
-            let mut blurred = gaussian_blur_f32(&edge_image, config.blur_sigma);
-
-            // add delta to each pixel so we dont loose mutations at spots which are not edges
-            blurred.pixels_mut().for_each(|pixel| {
-                *pixel = Luma([pixel.0[0] + 10]);
-            });
-
-            // Clip the values between 0 and 255
-            blurred.pixels_mut().for_each(|pixel| {
-                *pixel = Luma([pixel.0[0].clamp(0u8, 255u8)]);
-            });
-
-            // Scale the image between 0 and 255 (assuming the original values were not already scaled)
-            let max_val = blurred.iter().cloned().max().unwrap();
-            let scale = 255.0 / (max_val as f32);
-            blurred.pixels_mut().for_each(|pixel| {
-                *pixel = Luma([((pixel.0[0] as f32) * scale).round() as u8]);
-            });
-
-            // calculate per pixel weights
-            let weights = generate_pixel_edge_weights(&blurred);
-
-            let global_data = GlobalData {
-                rgb_image: &rgb_image,
-                euclidean_distance_map: &calculate_euclidean_distance_map_for_neighbors(&rgb_image),
-                edge_image: &edge_image,
-                width: rgb_image.width() as usize,
-                height: rgb_image.height() as usize,
-                pixel_weights: &weights,
-            };
-            run_genetic_algorithm_instance(&config, &global_data);
+            let rgb_image = Individual::open_image_as_rgb(&get_input_image_path(&config));
+            let export_directory = format!("./logs/preprocessing/{}", config.problem_instance);
+            let export_directory = config.preprocessing
+                .as_ref()
+                .filter(|preprocessing_config| preprocessing_config.export_intermediate_images)
+                .map(|_| Path::new(&export_directory));
+
+            // Without a pyramid only the full resolution level is used
+            let number_of_levels = config.pyramid
+                .as_ref()
+                .map(|pyramid_config| pyramid_config.number_of_levels.max(1))
+                .unwrap_or(1);
+            let mut population: Option<Population> = None;
+            let mut previous_dimensions = (0, 0);
+
+            for level in (0..number_of_levels).rev() {
+                let scale = 2_u32.pow(level as u32);
+                let level_image = if level == 0 {
+                    rgb_image.clone()
+                } else {
+                    resize(
+                        &rgb_image,
+                        (rgb_image.width() / scale).max(1),
+                        (rgb_image.height() / scale).max(1),
+                        FilterType::Triangle
+                    )
+                };
+                println!("Pyramid level {}: {}x{}", level, level_image.width(), level_image.height());
+
+                let image_data = ImageData::new(
+                    level_image,
+                    &config,
+                    if level == 0 {
+                        export_directory
+                    } else {
+                        None
+                    }
+                );
+                let global_data = image_data.get_global_data();
+
+                let mut level_config = config.clone();
+                if let Some(pyramid_config) = &config.pyramid {
+                    if level > 0 {
+                        level_config.number_of_generations = pyramid_config.number_of_generations_per_coarse_level;
+                        level_config.animation = None;
+                    }
+                }
+
+                let initial_population = population
+                    .take()
+                    .map(|coarse_population| {
+                        upsample_population(
+                            &coarse_population,
+                            previous_dimensions.0,
+                            previous_dimensions.1,
+                            &level_config,
+                            &global_data
+                        )
+                    });
+                let level_population = run_genetic_algorithm_instance(
+                    &level_config,
+                    &global_data,
+                    initial_population
+                );
+
+                if level == 0 {
+                    export_results(level_population, &config, &global_data);
+                } else {
+                    previous_dimensions = (global_data.width, global_data.height);
+                    population = Some(level_population);
+                }
+            }
         });
         handles.push(handle);
     }
@@ -4,36 +4,52 @@ use queues::{ queue, IsQueue, Queue };
 use rand::{ thread_rng, Rng };
 
 use crate::{
-    config::Config,
+    config::{ Config, FunctionConfig },
+    distance::color_distance,
     global_data::GlobalData,
-    individual::{ Connection, Individual },
+    individual::{ Connection, Individual, MutationParameters },
     population::Population,
-    utils::get_edge_weighted_random_pixel_index,
+    region_adjacency_graph::RegionAdjacencyGraph,
+    utils::{
+        get_boundary_pixel_sampler,
+        get_edge_weighted_random_pixel_index,
+        get_number_of_applications,
+        get_standard_normal_sample,
+    },
+    weighted_sampler::WeightedSampler,
 };
 
+// distance of two colors which differ by 30 in every rgb channel. In the other color spaces eat_similar starts from
+// the picked pixel instead of the mean of its segment if they are further apart
+const RESET_MEAN_DISTANCE: f64 = 51.96;
+
+/**
+ * The segment of every pixel and the number of pixels of every segment, indexed by the segment id
+ */
+struct SegmentSizeMap {
+    cluster_map: Vec<Vec<usize>>,
+    segment_sizes: Vec<usize>,
+}
+
+fn get_segment_size_map(child: &Individual, global_data: &GlobalData) -> SegmentSizeMap {
+    let cluster_map = child.get_cluster_map(global_data.width as i64, global_data.height as i64);
+    // cluster ids are assigned consecutively starting with 1
+    let mut segment_sizes = vec![0; global_data.width * global_data.height + 1];
+    for segments in cluster_map.iter() {
+        for segment in segments.iter() {
+            segment_sizes[*segment] += 1;
+        }
+    }
+    SegmentSizeMap { cluster_map, segment_sizes }
+}
+
 fn get_biggest_segment_direction(
     index: usize,
-    child: &mut Individual,
+    segment_size_map: &SegmentSizeMap,
     global_data: &GlobalData,
     inverse: bool
 ) -> Connection {
-    let segmentation_map = child.get_cluster_map(
-        global_data.width as i64,
-        global_data.height as i64
-    );
-
-    let mut segmentation_size_map: HashMap<usize, u32> = HashMap::new();
-
-    for y in 0..global_data.height {
-        for x in 0..global_data.width {
-            segmentation_size_map
-                .entry(segmentation_map[y][x])
-                .and_modify(|segment_size| {
-                    *segment_size += 1;
-                })
-                .or_insert(1);
-        }
-    }
+    let segmentation_map = &segment_size_map.cluster_map;
 
     let mut highest = 0;
     let mut highest_direction = Connection::None;
@@ -60,7 +76,7 @@ fn get_biggest_segment_direction(
 
         let segment_id =
             segmentation_map[(row + position.0) as usize][(column + position.1) as usize];
-        let number_of_segments = *segmentation_size_map.get(&segment_id).unwrap();
+        let number_of_segments = segment_size_map.segment_sizes[segment_id];
 
         if number_of_segments > highest {
             highest = number_of_segments;
@@ -76,36 +92,55 @@ fn get_biggest_segment_direction(
     return if inverse { lowest_direction } else { highest_direction };
 }
 
-fn flip_to_biggest_segment(child: &mut Individual, global_data: &GlobalData) {
-    let index = get_edge_weighted_random_pixel_index(global_data);
-
-    let highest_direction = get_biggest_segment_direction(index, child, global_data, false);
+fn flip_to_biggest_segment(
+    child: &mut Individual,
+    segment_size_map: &SegmentSizeMap,
+    global_data: &GlobalData,
+    index: usize
+) {
+    let highest_direction = get_biggest_segment_direction(index, segment_size_map, global_data, false);
 
     child.genome[index] = highest_direction;
 }
 
-fn flip_to_smallest_segment(child: &mut Individual, global_data: &GlobalData) {
-    let index = get_edge_weighted_random_pixel_index(global_data);
-
-    let lowest_direction = get_biggest_segment_direction(index, child, global_data, true);
+fn flip_to_smallest_segment(
+    child: &mut Individual,
+    segment_size_map: &SegmentSizeMap,
+    global_data: &GlobalData,
+    index: usize
+) {
+    let lowest_direction = get_biggest_segment_direction(index, segment_size_map, global_data, true);
 
     child.genome[index] = lowest_direction;
 }
 
-fn is_pixel_within_variance(
+/**
+ * How much a pixel may differ from the mean to be connected by eat_similar
+ */
+enum SimilarityTolerance {
+    // the maximal absolute difference of every rgb channel
+    PerChannel((f64, f64, f64)),
+    // the maximal distance of the configured metric, used for the other color spaces
+    Distance(f64),
+}
+
+fn is_pixel_within_tolerance(
     pixel: &(f64, f64, f64),
     mean: &(f64, f64, f64),
-    variance: &(f64, f64, f64)
+    tolerance: &SimilarityTolerance,
+    global_data: &GlobalData
 ) -> bool {
-    // Calculate squared deviations from the mean for the new pixel
-    let diff_red = (pixel.0 - mean.0).abs();
-    let diff_green = (pixel.1 - mean.1).abs();
-    let diff_blue = (pixel.2 - mean.2).abs();
-
-    diff_red <= variance.0 && diff_green <= variance.1 && diff_blue <= variance.2
+    match tolerance {
+        SimilarityTolerance::PerChannel(variance) => {
+            let diff_red = (pixel.0 - mean.0).abs();
+            let diff_green = (pixel.1 - mean.1).abs();
+            let diff_blue = (pixel.2 - mean.2).abs();
 
-    // diff_red + diff_green + diff_blue <=
-    //     threshold * variance.0 + threshold * variance.1 + threshold * variance.2
+            diff_red <= variance.0 && diff_green <= variance.1 && diff_blue <= variance.2
+        }
+        SimilarityTolerance::Distance(distance) =>
+            color_distance(pixel, mean, global_data.distance_metric) <= *distance,
+    }
 }
 
 fn connect_similar_pixels(
@@ -113,7 +148,7 @@ fn connect_similar_pixels(
     child: &mut Individual,
     seen_pixels: &mut Vec<usize>,
     mean: &(f64, f64, f64),
-    variance: &(f64, f64, f64),
+    tolerance: &SimilarityTolerance,
     global_data: &GlobalData,
     max_depth: usize
 ) {
@@ -165,18 +200,8 @@ fn connect_similar_pixels(
                 continue;
             }
 
-            let current_pixel = global_data.rgb_image.get_pixel(column_new as u32, row_new as u32);
-            if
-                is_pixel_within_variance(
-                    &(
-                        current_pixel.0[0] as f64,
-                        current_pixel.0[1] as f64,
-                        current_pixel.0[2] as f64,
-                    ),
-                    mean,
-                    variance
-                )
-            {
+            let current_pixel = &global_data.color_map[row_new as usize][column_new as usize];
+            if is_pixel_within_tolerance(current_pixel, mean, tolerance, global_data) {
                 // if the pixel is similar. Redirect it to the current pixel
                 child.genome[new_index] = position.2;
                 pixel_queue.add(new_index).unwrap();
@@ -371,86 +396,53 @@ pub fn eat_similar(child: &mut Individual, percent_of_picture: f64, global_data:
     let column = (random_index % global_data.width) as i32;
     let row = (random_index / global_data.width) as i32;
 
-    let pixel = global_data.rgb_image.get_pixel(column as u32, row as u32);
+    let pixel = global_data.color_map[row as usize][column as usize];
     // *pixel = Rgb([0, 255, 255]);
 
-    // get the segment from the pixel
-    let segment_map = child.get_cluster_map(global_data.width as i64, global_data.height as i64);
-    let segment = segment_map[row as usize][column as usize];
+    // get the segment from the pixel and its color statistics
+    let region_adjacency_graph = RegionAdjacencyGraph::from_individual(child, global_data);
+    let segment = region_adjacency_graph.get_region_of_pixel(random_index);
+    let segment_node = region_adjacency_graph.nodes.get(&segment).unwrap();
 
-    let mut mean_pixel_color = (0.0, 0.0, 0.0);
-    let mut number_of_pixels_in_segment = 0;
+    let mean_pixel_color = segment_node.mean_color;
+    let variance_pixel_color = segment_node.color_variance;
 
-    // loop over every pixel of that segment
-    for y in 0..global_data.height {
-        for x in 0..global_data.width {
-            let current_segment = segment_map[y][x];
-            if current_segment != segment {
-                continue;
-            }
-
-            let current_pixel = global_data.rgb_image.get_pixel(x as u32, y as u32);
-
-            // Update mean
-            mean_pixel_color.0 += current_pixel.0[0] as f64;
-            mean_pixel_color.1 += current_pixel.0[1] as f64;
-            mean_pixel_color.2 += current_pixel.0[2] as f64;
+    let mut mean = (mean_pixel_color.0, mean_pixel_color.1, mean_pixel_color.2);
 
-            number_of_pixels_in_segment += 1;
+    let variance_random = thread_rng().gen_range(35.0..80.0);
+    let tolerance = if global_data.color_space == "rgb" {
+        //initial pixel has a too high varianze to the mean, so the segment is probably to big and faulty
+        if
+            (mean.0 - pixel.0).abs() >= 30.0 &&
+            (mean.1 - pixel.1).abs() >= 30.0 &&
+            (mean.2 - pixel.2).abs() >= 30.0
+        {
+            mean = pixel;
         }
-    }
-
-    // calculate mean pixel color
-    mean_pixel_color.0 /= number_of_pixels_in_segment as f64;
-    mean_pixel_color.1 /= number_of_pixels_in_segment as f64;
-    mean_pixel_color.2 /= number_of_pixels_in_segment as f64;
-
-    let mut variance_pixel_color = (0.0, 0.0, 0.0);
 
-    // loop over every pixel of that segment again to calculate variance
-    for y in 0..global_data.height {
-        for x in 0..global_data.width {
-            let current_segment = segment_map[y][x];
-            if current_segment != segment {
-                continue;
-            }
-
-            let current_pixel = global_data.rgb_image.get_pixel(x as u32, y as u32);
-
-            // calculate squared differences from mean
-            let diff_r = ((current_pixel.0[0] as f64) - mean_pixel_color.0).powi(2);
-            let diff_g = ((current_pixel.0[1] as f64) - mean_pixel_color.1).powi(2);
-            let diff_b = ((current_pixel.0[2] as f64) - mean_pixel_color.2).powi(2);
-
-            variance_pixel_color.0 += diff_r;
-            variance_pixel_color.1 += diff_g;
-            variance_pixel_color.2 += diff_b;
+        SimilarityTolerance::PerChannel((
+            variance_pixel_color.0.clamp(1.0, variance_random),
+            variance_pixel_color.1.clamp(1.0, variance_random),
+            variance_pixel_color.2.clamp(1.0, variance_random),
+        ))
+    } else {
+        // euclidean and delta E 76 distances have the units of the color channels and CIEDE2000 roughly as well, so
+        // the tolerance is the standard deviation of the segment colors instead of their variance. The limits were
+        // tuned for rgb and are scaled to the range of the color space
+        let color_space_scale = global_data.color_space_scale;
+        if color_distance(&mean, &pixel, global_data.distance_metric) >= RESET_MEAN_DISTANCE * color_space_scale {
+            mean = pixel;
         }
-    }
-
-    // divide by the number of pixels to get the variance
-    variance_pixel_color.0 /= number_of_pixels_in_segment as f64;
-    variance_pixel_color.1 /= number_of_pixels_in_segment as f64;
-    variance_pixel_color.2 /= number_of_pixels_in_segment as f64;
-
-    // let mean = (pixel.0[0] as f64, pixel.0[1] as f64, pixel.0[2] as f64);
-    let variance_random = thread_rng().gen_range(35.0..80.0);
-    let variance = (
-        variance_pixel_color.0.clamp(1.0, variance_random),
-        variance_pixel_color.1.clamp(1.0, variance_random),
-        variance_pixel_color.2.clamp(1.0, variance_random),
-    );
 
-    let mut mean = (mean_pixel_color.0, mean_pixel_color.1, mean_pixel_color.2);
-
-    //initial pixel has a too high varianze to the mean, so the segment is probably to big and faulty
-    if
-        (mean.0 - (pixel.0[0] as f64)).abs() >= 30.0 &&
-        (mean.1 - (pixel.0[1] as f64)).abs() >= 30.0 &&
-        (mean.2 - (pixel.0[2] as f64)).abs() >= 30.0
-    {
-        mean = (pixel.0[0] as f64, pixel.0[1] as f64, pixel.0[2] as f64);
-    }
+        let standard_deviation = (
+            variance_pixel_color.0 +
+            variance_pixel_color.1 +
+            variance_pixel_color.2
+        ).sqrt();
+        SimilarityTolerance::Distance(
+            standard_deviation.clamp(color_space_scale, variance_random * color_space_scale)
+        )
+    };
 
     // println!("Mean: {:?}", mean);
     // println!("Variance: {:?}", variance);
@@ -463,7 +455,7 @@ pub fn eat_similar(child: &mut Individual, percent_of_picture: f64, global_data:
         child,
         &mut vec![],
         &mean,
-        &variance,
+        &tolerance,
         global_data,
         max_depth
     );
@@ -471,8 +463,124 @@ pub fn eat_similar(child: &mut Individual, percent_of_picture: f64, global_data:
     // show(&test)
 }
 
-fn flip_one_bit(child: &mut Individual, global_data: &GlobalData) {
-    let index = get_edge_weighted_random_pixel_index(global_data);
+/**
+ * Merges the two adjacent segments with the smallest difference of their mean colors.
+ * The smaller segment is merged into the bigger one
+ */
+fn merge_similar_segments(child: &mut Individual, global_data: &GlobalData) {
+    let mut region_adjacency_graph = RegionAdjacencyGraph::from_individual(child, global_data);
+
+    let most_similar_pair = region_adjacency_graph.edges
+        .keys()
+        .map(|(region_a, region_b)| {
+            let distance = color_distance(
+                &region_adjacency_graph.nodes.get(region_a).unwrap().mean_color,
+                &region_adjacency_graph.nodes.get(region_b).unwrap().mean_color,
+                global_data.distance_metric
+            );
+            (*region_a, *region_b, distance)
+        })
+        .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+    if let Some((region_a, region_b, _)) = most_similar_pair {
+        let size_a = region_adjacency_graph.nodes.get(&region_a).unwrap().size;
+        let size_b = region_adjacency_graph.nodes.get(&region_b).unwrap().size;
+        if size_a < size_b {
+            region_adjacency_graph.merge_regions(child, region_a, region_b);
+        } else {
+            region_adjacency_graph.merge_regions(child, region_b, region_a);
+        }
+    }
+}
+
+/**
+ * Splits the segment with the highest color variance along its strongest internal edge of the edge image.
+ * The pixels on both sides of the edge pixel with the highest color difference across it are used as seeds and
+ * both parts are grown from them, edge pixels are assigned last so the split follows the edge.
+ * Segments without internal edge pixels are not split.
+ */
+fn split_high_variance_segment(
+    child: &mut Individual,
+    global_data: &GlobalData,
+    minimum_segment_size: usize
+) {
+    let mut region_adjacency_graph = RegionAdjacencyGraph::from_individual(child, global_data);
+    let width = global_data.width;
+    let height = global_data.height;
+
+    let highest_variance_region = region_adjacency_graph.nodes
+        .iter()
+        .filter(|(_, node)| node.size >= minimum_segment_size.max(2))
+        .max_by(|(_, a), (_, b)| {
+            let variance_a = a.color_variance.0 + a.color_variance.1 + a.color_variance.2;
+            let variance_b = b.color_variance.0 + b.color_variance.1 + b.color_variance.2;
+            variance_a.partial_cmp(&variance_b).unwrap()
+        })
+        .map(|(region, _)| *region);
+    let region = match highest_variance_region {
+        Some(region) => region,
+        None => {
+            return;
+        }
+    };
+
+    let is_edge_pixel = |index: usize| {
+        global_data.edge_image.get_pixel((index % width) as u32, (index / width) as u32)[0] > 0
+    };
+
+    // find the edge pixel with the highest color difference between the pixels on both sides of it
+    let mut strongest_edge: Option<(usize, usize, f64)> = None;
+    for pixel in region_adjacency_graph.nodes.get(&region).unwrap().pixels.iter() {
+        if !is_edge_pixel(*pixel) {
+            continue;
+        }
+        let column = pixel % width;
+        let row = pixel / width;
+        // horizontal and vertical pair of opposite neighbors
+        for (row_adjustment, column_adjustment) in [
+            (0, 1),
+            (1, 0),
+        ] {
+            if
+                row < row_adjustment ||
+                column < column_adjustment ||
+                row + row_adjustment >= height ||
+                column + column_adjustment >= width
+            {
+                continue;
+            }
+            let first_row = row - row_adjustment;
+            let first_column = column - column_adjustment;
+            let first_seed = first_row * width + first_column;
+            let second_seed = (row + row_adjustment) * width + column + column_adjustment;
+            if
+                region_adjacency_graph.get_region_of_pixel(first_seed) != region ||
+                region_adjacency_graph.get_region_of_pixel(second_seed) != region
+            {
+                continue;
+            }
+            let difference =
+                global_data.distance_map[first_row][first_column][3 + 2 * row_adjustment]
+                    [3 + 2 * column_adjustment];
+            if strongest_edge.is_none_or(|(_, _, strongest)| difference > strongest) {
+                strongest_edge = Some((first_seed, second_seed, difference));
+            }
+        }
+    }
+
+    if let Some((first_seed, second_seed, _)) = strongest_edge {
+        region_adjacency_graph.split_region(
+            child,
+            global_data,
+            region,
+            first_seed,
+            second_seed,
+            is_edge_pixel
+        );
+    }
+}
+
+fn flip_one_bit(child: &mut Individual, index: usize) {
     let new_connection = match rand::thread_rng().gen_range(0..5) {
         0 => Connection::None,
         1 => Connection::Up,
@@ -484,11 +592,15 @@ fn flip_one_bit(child: &mut Individual, global_data: &GlobalData) {
     child.genome[index] = new_connection;
 }
 
-fn flip_to_smallest_deviation(child: &mut Individual, global_data: &GlobalData, radius: usize) {
-    // Cant use radius 0. Because it would not look up anything
+fn flip_to_smallest_deviation(
+    child: &mut Individual,
+    global_data: &GlobalData,
+    index: usize,
+    radius: usize
+) {
+    // Cant use radius 0. Because it would not look up anything. The distance map only reaches 3 pixels
     assert_ne!(radius, 0);
-
-    let index = get_edge_weighted_random_pixel_index(global_data);
+    assert!(radius <= 3);
 
     let mut smallest_deviation = f64::INFINITY;
     let mut smallest_direction = Connection::None;
@@ -502,7 +614,8 @@ fn flip_to_smallest_deviation(child: &mut Individual, global_data: &GlobalData,
         let column = (index % global_data.width) as i32;
         let row = (index / global_data.width) as i32;
 
-        let mut direction_deviation = f64::INFINITY;
+        let mut direction_deviation = 0.0;
+        let mut number_of_looked_up_pixels = 0;
 
         for radius in 1..=radius as i32 {
             let pixel_y_offset = position.0 * (radius as i32);
@@ -518,11 +631,16 @@ fn flip_to_smallest_deviation(child: &mut Individual, global_data: &GlobalData,
             }
 
             direction_deviation +=
-                global_data.euclidean_distance_map[row as usize][column as usize]
+                global_data.distance_map[row as usize][column as usize]
                     [(pixel_y_offset + 3) as usize][(pixel_x_offset + 3) as usize];
+            number_of_looked_up_pixels += 1;
         }
 
-        direction_deviation /= radius as f64;
+        // directions leaving the image are never chosen
+        if number_of_looked_up_pixels == 0 {
+            continue;
+        }
+        direction_deviation /= number_of_looked_up_pixels as f64;
 
         if direction_deviation < smallest_deviation {
             smallest_deviation = direction_deviation;
@@ -533,56 +651,201 @@ fn flip_to_smallest_deviation(child: &mut Individual, global_data: &GlobalData,
     child.genome[index] = smallest_direction;
 }
 
+// These mutations change a single gene, so they can be applied with a per gene probability
+const GENE_MUTATIONS: [&str; 4] = [
+    "flip_one_bit",
+    "flip_to_smallest_segment",
+    "flip_to_biggest_segment",
+    "flip_to_smallest_deviation",
+];
+
+/**
+ * Returns the configured value of a parameter of the first mutation with the given name
+ */
+fn get_configured_parameter(
+    config: &Config,
+    name: &str,
+    parameter: impl Fn(&FunctionConfig) -> Option<f64>,
+    default: f64
+) -> f64 {
+    config.mutations
+        .iter()
+        .find(|mutation_config| mutation_config.name == name)
+        .and_then(parameter)
+        .unwrap_or(default)
+}
+
+/**
+ * Mutates the parameters of the individual log-normally and keeps them in a sensible range.
+ * Individuals without parameters start at the configured values.
+ */
+fn self_adapt_parameters(child: &mut Individual, config: &Config, learning_rate: f64) {
+    let parameters = child.mutation_parameters.get_or_insert_with(|| MutationParameters {
+        max_depth_percent_of_picture: get_configured_parameter(
+            config,
+            "eat_similar",
+            |mutation_config| mutation_config.max_depth_percent_of_picture,
+            0.1
+        ),
+        radius: get_configured_parameter(
+            config,
+            "flip_to_smallest_deviation",
+            |mutation_config| mutation_config.radius.map(|radius| radius as f64),
+            1.0
+        ),
+        minimum_coverage_percentage: get_configured_parameter(
+            config,
+            "destroy_small_segments",
+            |mutation_config| mutation_config.minimum_coverage_percentage,
+            0.1
+        ),
+    });
+
+    parameters.max_depth_percent_of_picture = (
+        parameters.max_depth_percent_of_picture * (learning_rate * get_standard_normal_sample()).exp()
+    ).clamp(0.001, 1.0);
+    // the distance map only reaches 3 pixels in every direction
+    parameters.radius = (parameters.radius * (learning_rate * get_standard_normal_sample()).exp()).clamp(
+        1.0,
+        3.0
+    );
+    parameters.minimum_coverage_percentage = (
+        parameters.minimum_coverage_percentage * (learning_rate * get_standard_normal_sample()).exp()
+    ).clamp(0.0001, 1.0);
+}
+
+/**
+ * Applies the mutation to the child. The single gene mutations pick their pixel with the given sampler,
+ * or edge weighted if there is none.
+ */
+fn apply_mutation(
+    child: &mut Individual,
+    mutation_config: &FunctionConfig,
+    global_data: &GlobalData,
+    pixel_sampler: Option<&WeightedSampler>,
+    segment_size_map: Option<&SegmentSizeMap>
+) {
+    // the parameters of the individual replace the configured ones if self adaptation is enabled
+    let parameters = child.mutation_parameters;
+    let get_pixel_index = || {
+        match pixel_sampler {
+            Some(pixel_sampler) => pixel_sampler.sample(),
+            None => get_edge_weighted_random_pixel_index(global_data),
+        }
+    };
+    match mutation_config.name.as_str() {
+        "flip_one_bit" => {
+            flip_one_bit(child, get_pixel_index());
+        }
+        "flip_to_smallest_segment" => {
+            flip_to_smallest_segment(child, segment_size_map.unwrap(), global_data, get_pixel_index());
+        }
+        "flip_to_biggest_segment" => {
+            flip_to_biggest_segment(child, segment_size_map.unwrap(), global_data, get_pixel_index());
+        }
+        "eat_similar" => {
+            let max_depth_percent_of_picture = match parameters {
+                Some(parameters) => parameters.max_depth_percent_of_picture,
+                None => mutation_config.max_depth_percent_of_picture.unwrap(),
+            };
+            eat_similar(child, max_depth_percent_of_picture, global_data);
+        }
+        "flip_to_smallest_deviation" => {
+            let radius = match parameters {
+                Some(parameters) => parameters.radius.round() as usize,
+                None => mutation_config.radius.unwrap_or(1),
+            };
+            flip_to_smallest_deviation(child, global_data, get_pixel_index(), radius);
+        }
+        "destroy_small_segments" => {
+            let minimum_coverage_percentage = match parameters {
+                Some(parameters) => parameters.minimum_coverage_percentage,
+                None => mutation_config.minimum_coverage_percentage.unwrap(),
+            };
+            destroy_small_segments(child, global_data, minimum_coverage_percentage);
+        }
+        "merge_similar_segments" => {
+            merge_similar_segments(child, global_data);
+        }
+        "split_high_variance_segment" => {
+            split_high_variance_segment(
+                child,
+                global_data,
+                mutation_config.minimum_segment_size.unwrap_or(2)
+            );
+        }
+        _ =>
+            panic!(
+                "Didn't have an Implementation for mutation function: {:?}",
+                mutation_config.name.as_str()
+            ),
+    }
+}
+
 pub fn mutate(population: &mut Population, config: &Config, global_data: &GlobalData) {
-    let mut rng = rand::thread_rng();
-    for mutation_config in config.mutations.iter() {
-        // Calculate the number of crossovers which should happen for the specific config
-        let number_of_mutations: u64 = (
-            (config.population_size as f64) * mutation_config.probability.unwrap()
-        ).ceil() as u64;
-
-        for _ in 0..number_of_mutations {
-            let individual_index: usize = rng.gen_range(0..config.population_size);
-            let child = &mut population[individual_index];
-            match mutation_config.name.as_str() {
-                "flip_one_bit" => {
-                    flip_one_bit(child, global_data);
-                }
-                "flip_to_smallest_segment" => {
-                    flip_to_smallest_segment(child, global_data);
-                }
-                "flip_to_biggest_segment" => {
-                    flip_to_biggest_segment(child, global_data);
-                }
-                "eat_similar" => {
-                    eat_similar(
-                        child,
-                        mutation_config.max_depth_percent_of_picture.unwrap(),
+    // the strategy parameters are mutated before they are used
+    if let Some(self_adaptation_config) = &config.self_adaptation {
+        for child in population.iter_mut() {
+            self_adapt_parameters(child, config, self_adaptation_config.learning_rate);
+        }
+    }
+
+    // the boundary samplers are built once per child and generation, when the first single gene mutation needs them.
+    // Single gene mutations barely move the boundaries
+    let mut pixel_samplers: Vec<Option<WeightedSampler>> = population
+        .iter()
+        .map(|_| None)
+        .collect();
+
+    for (mutation_index, mutation_config) in config.mutations.iter().enumerate() {
+        if
+            mutation_config.probability_mode.as_deref() == Some("per_gene") &&
+            !GENE_MUTATIONS.contains(&mutation_config.name.as_str())
+        {
+            panic!("The mutation {:?} can't be applied per gene", mutation_config.name.as_str());
+        }
+
+        let number_of_applications = get_number_of_applications(
+            mutation_config,
+            population.len(),
+            global_data.width * global_data.height
+        );
+        let uses_boundary_sampling =
+            config.boundary_sampling_weight > 0.0 &&
+            GENE_MUTATIONS.contains(&mutation_config.name.as_str());
+        let uses_segment_sizes =
+            mutation_config.name == "flip_to_smallest_segment" || mutation_config.name == "flip_to_biggest_segment";
+        for (individual_index, applications) in number_of_applications.into_iter().enumerate() {
+            if uses_boundary_sampling && applications > 0 && pixel_samplers[individual_index].is_none() {
+                pixel_samplers[individual_index] = Some(
+                    get_boundary_pixel_sampler(
+                        &population[individual_index],
+                        config.boundary_sampling_weight,
                         global_data
-                    );
-                }
-                "flip_to_smallest_deviation" => {
-                    flip_to_smallest_deviation(
-                        child,
-                        global_data,
-                        mutation_config.radius.unwrap_or(1)
-                    );
-                }
-                "destroy_small_segments" => {
-                    destroy_small_segments(
-                        child,
-                        global_data,
-                        mutation_config.minimum_coverage_percentage.unwrap()
-                    );
-                }
-                _ =>
-                    panic!(
-                        "Didn't have an Implementation for mutation function: {:?}",
-                        mutation_config.name.as_str()
-                    ),
+                    )
+                );
+            }
+            let pixel_sampler = if uses_boundary_sampling { pixel_samplers[individual_index].as_ref() } else { None };
+            // the segment sizes are calculated once per child and operator, instead of decoding the genome for every
+            // flipped gene
+            let segment_size_map = if uses_segment_sizes && applications > 0 {
+                Some(get_segment_size_map(&population[individual_index], global_data))
+            } else {
+                None
+            };
+            for _ in 0..applications {
+                apply_mutation(
+                    &mut population[individual_index],
+                    mutation_config,
+                    global_data,
+                    pixel_sampler,
+                    segment_size_map.as_ref()
+                );
+            }
+            if applications > 0 {
+                population[individual_index].set_needs_update();
+                population[individual_index].applied_mutations.push(mutation_index);
             }
-
-            population[individual_index].set_needs_update();
         }
     }
 }
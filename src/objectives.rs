@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+
+use crate::{
+    config::{ Config, ObjectiveConfig },
+    distance::{ color_distance, get_nearest_neighbor_value },
+    global_data::GlobalData,
+    texture::{ texture_distance, NUMBER_OF_TEXTURE_FEATURES },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectiveDirection {
+    Maximize,
+    Minimize,
+}
+
+impl ObjectiveDirection {
+    /**
+     * Returns true if value a is strictly better than value b in this direction
+     */
+    pub fn is_better(&self, a: f64, b: f64) -> bool {
+        match self {
+            ObjectiveDirection::Maximize => a > b,
+            ObjectiveDirection::Minimize => a < b,
+        }
+    }
+
+    // the sign the objective value gets in the weighted fitness (higher weighted fitness is better)
+    pub fn sign(&self) -> f64 {
+        match self {
+            ObjectiveDirection::Maximize => 1.0,
+            ObjectiveDirection::Minimize => -1.0,
+        }
+    }
+}
+
+pub trait Objective: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn default_direction(&self) -> ObjectiveDirection;
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], config: &Config, global_data: &GlobalData) -> f64;
+}
+
+pub struct WeightedObjective {
+    pub objective: Box<dyn Objective>,
+    pub direction: ObjectiveDirection,
+    pub multiplier: f64,
+}
+
+/**
+ * Returns the texture weights (color, deviation, edge). Without a texture config only the color is used
+ */
+fn get_texture_weights(config: &Config, global_data: &GlobalData) -> (f64, f64, f64) {
+    match &config.texture {
+        Some(texture_config) if global_data.texture_map.is_some() =>
+            (texture_config.color_weight, texture_config.deviation_weight, texture_config.edge_weight),
+        _ => (1.0, 0.0, 0.0),
+    }
+}
+
+/**
+ * Calls the function for every pair of a pixel and one of its 8 neighbors which are not in the same segment
+ */
+fn for_each_segment_crossing_neighbor(
+    cluster_map: &[Vec<usize>],
+    global_data: &GlobalData,
+    mut function: impl FnMut(usize, usize, i32, i32)
+) {
+    for row in 0..global_data.height {
+        for column in 0..global_data.width {
+            for x_offset in -1_i32..=1 {
+                for y_offset in -1_i32..=1 {
+                    if
+                        (row == 0 && y_offset == -1) ||
+                        (row == global_data.height - 1 && y_offset == 1) ||
+                        (column == 0 && x_offset == -1) ||
+                        (column == global_data.width - 1 && x_offset == 1)
+                    {
+                        continue;
+                    }
+                    // check if the pixel is in the same cluster. If yes ignore it
+                    if
+                        cluster_map[row][column] ==
+                        cluster_map[((row as i32) + y_offset) as usize]
+                            [((column as i32) + x_offset) as usize]
+                    {
+                        continue;
+                    }
+                    function(row, column, x_offset, y_offset);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Edge Value := sum of the color distance for all the neighbors which are not in the same segment
+ */
+pub struct EdgeValue;
+
+impl Objective for EdgeValue {
+    fn name(&self) -> &str {
+        "edge_value"
+    }
+
+    fn default_direction(&self) -> ObjectiveDirection {
+        ObjectiveDirection::Maximize
+    }
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], config: &Config, global_data: &GlobalData) -> f64 {
+        let (color_weight, _, texture_edge_weight) = get_texture_weights(config, global_data);
+        let mut edge_value = 0.0;
+        for_each_segment_crossing_neighbor(cluster_map, global_data, |row, column, x_offset, y_offset| {
+            edge_value +=
+                color_weight *
+                global_data.distance_map[row][column][(y_offset + 3) as usize]
+                    [(x_offset + 3) as usize];
+            if texture_edge_weight != 0.0 {
+                let texture_map = global_data.texture_map.unwrap();
+                edge_value +=
+                    texture_edge_weight *
+                    texture_distance(
+                        &texture_map[row][column],
+                        &texture_map[((row as i32) + y_offset) as usize]
+                            [((column as i32) + x_offset) as usize]
+                    );
+            }
+        });
+        edge_value
+    }
+}
+
+/**
+ * Connectivity := sum of 1 / F(j) for all the neighbors j which are not in the same segment
+ */
+pub struct Connectivity;
+
+impl Objective for Connectivity {
+    fn name(&self) -> &str {
+        "connectivity"
+    }
+
+    fn default_direction(&self) -> ObjectiveDirection {
+        ObjectiveDirection::Minimize
+    }
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], _config: &Config, global_data: &GlobalData) -> f64 {
+        let mut connectivity = 0.0;
+        for_each_segment_crossing_neighbor(cluster_map, global_data, |_, _, x_offset, y_offset| {
+            connectivity += 1.0 / (get_nearest_neighbor_value(x_offset, y_offset) as f64);
+        });
+        connectivity
+    }
+}
+
+/**
+ * Overall Deviation := sum of the distances of all pixels to the centroid of their segment
+ */
+pub struct OverallDeviation;
+
+impl Objective for OverallDeviation {
+    fn name(&self) -> &str {
+        "overall_deviation"
+    }
+
+    fn default_direction(&self) -> ObjectiveDirection {
+        ObjectiveDirection::Minimize
+    }
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], config: &Config, global_data: &GlobalData) -> f64 {
+        let (color_weight, texture_deviation_weight, _) = get_texture_weights(config, global_data);
+        let color_map = global_data.color_map;
+
+        // Map which holds the sums for each color for all pixels in one cluster (key: cluster_id, (sum_r, sum_g, sum_b, number_of_pixels))
+        let mut overall_deviation_map: HashMap<usize, (f64, f64, f64, u32)> = HashMap::new();
+        // Map which holds the sums of the texture features for all pixels in one cluster
+        let mut texture_sum_map: HashMap<usize, Vec<f64>> = HashMap::new();
+
+        for row in 0..global_data.height {
+            for column in 0..global_data.width {
+                // aggregate all the pixels colors for one image to calculate the centroid later
+                let pixel = color_map[row][column];
+                overall_deviation_map
+                    .entry(cluster_map[row][column])
+                    .and_modify(|(sum_red, sum_green, sum_blue, count)| {
+                        *sum_red += pixel.0;
+                        *sum_green += pixel.1;
+                        *sum_blue += pixel.2;
+                        *count += 1;
+                    })
+                    .or_insert((pixel.0, pixel.1, pixel.2, 1));
+
+                if texture_deviation_weight != 0.0 {
+                    let texture = &global_data.texture_map.unwrap()[row][column];
+                    let texture_sum = texture_sum_map
+                        .entry(cluster_map[row][column])
+                        .or_insert(vec![0.0; NUMBER_OF_TEXTURE_FEATURES]);
+                    for (sum, feature) in texture_sum.iter_mut().zip(texture.iter()) {
+                        *sum += feature;
+                    }
+                }
+            }
+        }
+
+        // calculate the centroid color for each cluster by consuming the aggregated map
+        let mut cluster_centroid_map: HashMap<usize, (f64, f64, f64)> = HashMap::new();
+        let mut texture_centroid_map: HashMap<usize, Vec<f64>> = HashMap::new();
+        for (cluster_id, (sum_red, sum_green, sum_blue, count)) in overall_deviation_map {
+            cluster_centroid_map.insert(cluster_id, (
+                sum_red / (count as f64),
+                sum_green / (count as f64),
+                sum_blue / (count as f64),
+            ));
+            if let Some(texture_sum) = texture_sum_map.get(&cluster_id) {
+                texture_centroid_map.insert(
+                    cluster_id,
+                    texture_sum
+                        .iter()
+                        .map(|sum| sum / (count as f64))
+                        .collect()
+                );
+            }
+        }
+
+        // formular states iterate of all pixels in all segments, which translates to loop over all pixels and get the segment for the pixel
+        // for every pixel get the distance to the centroid pixel and add it to the deviation
+        let mut overall_deviation = 0.0;
+        for row in 0..global_data.height {
+            for column in 0..global_data.width {
+                let current_pixel = &color_map[row][column];
+                let centroid_pixel = cluster_centroid_map.get(&cluster_map[row][column]).unwrap();
+                overall_deviation +=
+                    color_weight *
+                    color_distance(current_pixel, centroid_pixel, global_data.distance_metric);
+
+                if texture_deviation_weight != 0.0 {
+                    let texture_centroid = texture_centroid_map
+                        .get(&cluster_map[row][column])
+                        .unwrap();
+                    overall_deviation +=
+                        texture_deviation_weight *
+                        texture_distance(
+                            &global_data.texture_map.unwrap()[row][column],
+                            texture_centroid
+                        );
+                }
+            }
+        }
+        overall_deviation
+    }
+}
+
+/**
+ * Number of segments in the segmentation
+ */
+pub struct SegmentCount;
+
+impl Objective for SegmentCount {
+    fn name(&self) -> &str {
+        "segment_count"
+    }
+
+    fn default_direction(&self) -> ObjectiveDirection {
+        ObjectiveDirection::Minimize
+    }
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], _config: &Config, _global_data: &GlobalData) -> f64 {
+        // cluster ids are assigned consecutively starting with 1
+        cluster_map
+            .iter()
+            .map(|row| *row.iter().max().unwrap())
+            .max()
+            .unwrap() as f64
+    }
+}
+
+/**
+ * Number of horizontally or vertically neighboring pixel pairs which are in different segments
+ */
+pub struct BoundaryLength;
+
+impl Objective for BoundaryLength {
+    fn name(&self) -> &str {
+        "boundary_length"
+    }
+
+    fn default_direction(&self) -> ObjectiveDirection {
+        ObjectiveDirection::Minimize
+    }
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], _config: &Config, global_data: &GlobalData) -> f64 {
+        let mut boundary_length = 0;
+        for row in 0..global_data.height {
+            for column in 0..global_data.width {
+                if
+                    column < global_data.width - 1 &&
+                    cluster_map[row][column] != cluster_map[row][column + 1]
+                {
+                    boundary_length += 1;
+                }
+                if
+                    row < global_data.height - 1 &&
+                    cluster_map[row][column] != cluster_map[row + 1][column]
+                {
+                    boundary_length += 1;
+                }
+            }
+        }
+        boundary_length as f64
+    }
+}
+
+/**
+ * Share of the segment border pixels which lie on (or directly next to) an edge of the canny edge image
+ */
+pub struct EdgeMapAgreement;
+
+impl Objective for EdgeMapAgreement {
+    fn name(&self) -> &str {
+        "edge_map_agreement"
+    }
+
+    fn default_direction(&self) -> ObjectiveDirection {
+        ObjectiveDirection::Maximize
+    }
+
+    fn evaluate(&self, cluster_map: &[Vec<usize>], _config: &Config, global_data: &GlobalData) -> f64 {
+        let mut number_of_border_pixels = 0;
+        let mut number_of_agreeing_pixels = 0;
+        for row in 0..global_data.height {
+            for column in 0..global_data.width {
+                let is_border =
+                    (column < global_data.width - 1 &&
+                        cluster_map[row][column] != cluster_map[row][column + 1]) ||
+                    (row < global_data.height - 1 &&
+                        cluster_map[row][column] != cluster_map[row + 1][column]);
+                if !is_border {
+                    continue;
+                }
+                number_of_border_pixels += 1;
+
+                // allow the edge to be one pixel off
+                let mut is_on_edge = false;
+                for y in row.saturating_sub(1)..=(row + 1).min(global_data.height - 1) {
+                    for x in column.saturating_sub(1)..=(column + 1).min(global_data.width - 1) {
+                        if global_data.edge_image.get_pixel(x as u32, y as u32)[0] > 0 {
+                            is_on_edge = true;
+                        }
+                    }
+                }
+                if is_on_edge {
+                    number_of_agreeing_pixels += 1;
+                }
+            }
+        }
+
+        if number_of_border_pixels == 0 {
+            return 0.0;
+        }
+        (number_of_agreeing_pixels as f64) / (number_of_border_pixels as f64)
+    }
+}
+
+fn get_objective_by_name(name: &str) -> Box<dyn Objective> {
+    match name {
+        "edge_value" => Box::new(EdgeValue),
+        "connectivity" => Box::new(Connectivity),
+        "overall_deviation" => Box::new(OverallDeviation),
+        "segment_count" => Box::new(SegmentCount),
+        "boundary_length" => Box::new(BoundaryLength),
+        "edge_map_agreement" => Box::new(EdgeMapAgreement),
+        _ => panic!("Didn't have an Implementation for objective: {:?}", name),
+    }
+}
+
+/**
+ * Builds the list of objectives from the config. If no objectives are configured the three objectives
+ * of the exercise are used with the multipliers from the config
+ */
+pub fn initialize_objectives(config: &Config) -> Vec<WeightedObjective> {
+    let objective_configs: Vec<ObjectiveConfig> = match &config.objectives {
+        Some(objective_configs) => objective_configs.clone(),
+        None =>
+            vec![
+                ObjectiveConfig {
+                    name: "edge_value".to_string(),
+                    direction: None,
+                    multiplier: Some(config.edge_value_multiplier),
+                },
+                ObjectiveConfig {
+                    name: "connectivity".to_string(),
+                    direction: None,
+                    multiplier: Some(config.connectivity_multiplier),
+                },
+                ObjectiveConfig {
+                    name: "overall_deviation".to_string(),
+                    direction: None,
+                    multiplier: Some(config.overall_deviation_multiplier),
+                }
+            ],
+    };
+
+    if objective_configs.is_empty() {
+        panic!("At least one objective needs to be configured.");
+    }
+
+    // the contact sheet is only exported at the end, so check its sort objective before running the algorithm
+    if let Some(contact_sheet_config) = &config.contact_sheet {
+        if
+            !objective_configs
+                .iter()
+                .any(|objective_config| objective_config.name == contact_sheet_config.sort_objective)
+        {
+            panic!(
+                "Contact sheet sort objective {:?} is not one of the configured objectives",
+                contact_sheet_config.sort_objective
+            );
+        }
+    }
+
+    objective_configs
+        .iter()
+        .map(|objective_config| {
+            let objective = get_objective_by_name(&objective_config.name);
+            let direction = match objective_config.direction.as_deref() {
+                None => objective.default_direction(),
+                Some("maximize") => ObjectiveDirection::Maximize,
+                Some("minimize") => ObjectiveDirection::Minimize,
+                Some(direction) =>
+                    panic!("Invalid direction {:?} for objective {:?}", direction, objective_config.name),
+            };
+            WeightedObjective {
+                objective,
+                direction,
+                multiplier: objective_config.multiplier.unwrap_or(1.0),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,163 @@
+use crate::config::{ AdaptiveOperatorSelectionConfig, FunctionConfig };
+
+/**
+ * Adapts the application rates of a list of operators during the run. The sum of the configured probabilities stays
+ * the same, only its distribution over the operators changes. The reward of an operator is the share of its
+ * offspring which survived the survivor selection.
+ */
+pub struct AdaptiveOperatorSelection {
+    method: String,
+    minimum_probability: f64,
+    adaptation_rate: f64,
+    learning_rate: f64,
+    total_probability: f64,
+    names: Vec<String>,
+    qualities: Vec<f64>,
+    probabilities: Vec<f64>,
+}
+
+impl AdaptiveOperatorSelection {
+    pub fn new(
+        operator_configs: &[FunctionConfig],
+        selection_config: &AdaptiveOperatorSelectionConfig
+    ) -> AdaptiveOperatorSelection {
+        let number_of_operators = operator_configs.len();
+        let total_probability: f64 = operator_configs
+            .iter()
+            .map(|operator_config| operator_config.probability.unwrap())
+            .sum();
+
+        // start with the configured distribution
+        let probabilities: Vec<f64> = operator_configs
+            .iter()
+            .map(|operator_config| {
+                if total_probability > 0.0 {
+                    operator_config.probability.unwrap() / total_probability
+                } else {
+                    1.0 / (number_of_operators as f64)
+                }
+            })
+            .collect();
+
+        // every operator needs to keep a chance to be applied
+        let minimum_probability = selection_config.minimum_probability.min(
+            1.0 / (number_of_operators.max(1) as f64)
+        );
+
+        AdaptiveOperatorSelection {
+            method: selection_config.method.clone(),
+            minimum_probability,
+            adaptation_rate: selection_config.adaptation_rate,
+            learning_rate: selection_config.learning_rate,
+            total_probability,
+            names: operator_configs
+                .iter()
+                .map(|operator_config| operator_config.name.clone())
+                .collect(),
+            qualities: probabilities.clone(),
+            probabilities,
+        }
+    }
+
+    /**
+     * Sets the probabilities of the operators to their current share of the total probability
+     */
+    pub fn apply_probabilities(&self, operator_configs: &mut [FunctionConfig]) {
+        for (operator_config, probability) in operator_configs.iter_mut().zip(self.probabilities.iter()) {
+            operator_config.probability = Some(self.total_probability * probability);
+        }
+    }
+
+    /**
+     * Updates the qualities with the rewards of this generation and recalculates the probabilities.
+     * Operators without a reward were not applied and keep their quality.
+     */
+    pub fn update(&mut self, rewards: &[Option<f64>]) {
+        for (quality, reward) in self.qualities.iter_mut().zip(rewards.iter()) {
+            if let Some(reward) = reward {
+                *quality += self.adaptation_rate * (reward - *quality);
+            }
+        }
+
+        let number_of_operators = self.probabilities.len() as f64;
+        match self.method.as_str() {
+            "probability_matching" => {
+                let sum_of_qualities: f64 = self.qualities.iter().sum();
+                for (probability, quality) in self.probabilities.iter_mut().zip(self.qualities.iter()) {
+                    *probability = if sum_of_qualities > 0.0 {
+                        self.minimum_probability +
+                            (1.0 - number_of_operators * self.minimum_probability) *
+                                (quality / sum_of_qualities)
+                    } else {
+                        1.0 / number_of_operators
+                    };
+                }
+            }
+            "adaptive_pursuit" => {
+                let maximum_probability = 1.0 - (number_of_operators - 1.0) * self.minimum_probability;
+                let best_operator = self.qualities
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                    .map(|(index, _)| index)
+                    .unwrap();
+                for (index, probability) in self.probabilities.iter_mut().enumerate() {
+                    let target = if index == best_operator {
+                        maximum_probability
+                    } else {
+                        self.minimum_probability
+                    };
+                    *probability += self.learning_rate * (target - *probability);
+                }
+            }
+            _ =>
+                panic!(
+                    "Didn't have an Implementation for adaptive operator selection: {:?}",
+                    self.method.as_str()
+                ),
+        }
+    }
+
+    pub fn log_probabilities(&self, label: &str) {
+        let rates: Vec<String> = self.names
+            .iter()
+            .zip(self.probabilities.iter())
+            .map(|(name, probability)| format!("{}: {:.3}", name, self.total_probability * probability))
+            .collect();
+        println!("{} rates: {}", label, rates.join(" | "));
+    }
+}
+
+/**
+ * Calculates the share of the offspring of every operator which survived. None if the operator created no offspring.
+ */
+pub fn get_operator_rewards(
+    number_of_operators: usize,
+    children_operators: Vec<&Vec<usize>>,
+    survivor_operators: Vec<&Vec<usize>>
+) -> Vec<Option<f64>> {
+    let mut number_of_children = vec![0; number_of_operators];
+    let mut number_of_survivors = vec![0; number_of_operators];
+    for operators in children_operators {
+        for operator in operators {
+            number_of_children[*operator] += 1;
+        }
+    }
+    for operators in survivor_operators {
+        for operator in operators {
+            number_of_survivors[*operator] += 1;
+        }
+    }
+    number_of_children
+        .iter()
+        .zip(number_of_survivors.iter())
+        .map(|(children, survivors)| {
+            if *children > 0 {
+                // survivors can be selected more than once
+                Some(((*survivors as f64) / (*children as f64)).min(1.0))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
@@ -1,10 +1,21 @@
-use crate::{ config::Config, global_data::GlobalData, individual::{ get_mst_genome, Individual } };
+use crate::{
+    config::{ get_output_directory, Config },
+    global_data::GlobalData,
+    individual::{
+        get_genome_from_label_map,
+        get_mst_genome,
+        Connection,
+        Individual,
+        MstEdge,
+    },
+    superpixels::{ get_felzenszwalb_label_map, get_slic_label_map },
+};
 
 use std::fs::{ create_dir_all, read_dir, remove_dir, remove_file };
 
 use std::path::Path;
 
-use image::ImageResult;
+use image::{ ImageResult, RgbImage };
 use rand::Rng;
 
 pub type Population = Vec<Individual>;
@@ -22,12 +33,80 @@ pub fn initialize_population(config: &Config, global_data: &GlobalData) -> Popul
             }
         }
         "mst" => {
+            // The MST only depends on the image, so it is calculated once and every individual removes a different
+            // number of the heaviest edges. This spreads the initial population over different segment counts
+            let mut mst_edges = vec![];
+            let mst_genome = get_mst_genome(
+                global_data.distance_map,
+                global_data.width,
+                global_data.height,
+                Some(&mut mst_edges)
+            );
+            let removable_edges = get_removable_mst_edges(
+                &mst_edges,
+                global_data.width * global_data.height,
+                config.mst_minimum_segment_size,
+                config.mst_maximum_removed_edges
+            );
+            let maximum_removed_edges = config.mst_maximum_removed_edges.min(removable_edges.len());
+
+            for individual_index in 0..config.population_size {
+                let number_of_removed_edges = if config.population_size > 1 {
+                    (
+                        ((individual_index * maximum_removed_edges) as f64) /
+                        ((config.population_size - 1) as f64)
+                    ).round() as usize
+                } else {
+                    0
+                };
+                let mut genome = mst_genome.clone();
+                for pixel_index in removable_edges.iter().take(number_of_removed_edges) {
+                    genome[*pixel_index] = Connection::None;
+                }
+                let mut individual = Individual::new_with_genome(&genome);
+                individual.update_objectives(config, global_data);
+                population.push(individual);
+            }
+        }
+        "slic" => {
+            let slic_config = config.slic.as_ref().expect("The slic initialization needs a slic config");
             for _ in 0..config.population_size {
-                let genome = get_mst_genome(
-                    global_data.rgb_image,
-                    global_data.euclidean_distance_map
+                let number_of_superpixels = rand
+                    ::thread_rng()
+                    .gen_range(
+                        slic_config.minimum_number_of_superpixels..=slic_config.maximum_number_of_superpixels
+                    );
+                let compactness = rand
+                    ::thread_rng()
+                    .gen_range(slic_config.minimum_compactness..=slic_config.maximum_compactness);
+                let label_map = get_slic_label_map(
+                    global_data.color_map,
+                    global_data.distance_metric,
+                    number_of_superpixels,
+                    compactness,
+                    slic_config.number_of_iterations
                 );
-                let mut individual = Individual::new_with_genome(&genome);
+                let mut individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+                individual.update_objectives(config, global_data);
+                population.push(individual);
+            }
+        }
+        "felzenszwalb" => {
+            let felzenszwalb_config = config.felzenszwalb
+                .as_ref()
+                .expect("The felzenszwalb initialization needs a felzenszwalb config");
+            for _ in 0..config.population_size {
+                let scale = rand
+                    ::thread_rng()
+                    .gen_range(felzenszwalb_config.minimum_scale..=felzenszwalb_config.maximum_scale);
+                let label_map = get_felzenszwalb_label_map(
+                    global_data.distance_map,
+                    global_data.width,
+                    global_data.height,
+                    scale,
+                    felzenszwalb_config.minimum_segment_size
+                );
+                let mut individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
                 individual.update_objectives(config, global_data);
                 population.push(individual);
             }
@@ -40,6 +119,135 @@ pub fn initialize_population(config: &Config, global_data: &GlobalData) -> Popul
     population
 }
 
+/**
+ * Scales the segmentations of a population from a coarser image up to the image of the global data with nearest
+ * neighbor sampling and converts them back into genomes
+ */
+pub fn upsample_population(
+    population: &Population,
+    coarse_width: usize,
+    coarse_height: usize,
+    config: &Config,
+    global_data: &GlobalData
+) -> Population {
+    population
+        .iter()
+        .map(|coarse_individual| {
+            let coarse_cluster_map = coarse_individual.get_cluster_map(
+                coarse_width as i64,
+                coarse_height as i64
+            );
+            let label_map: Vec<Vec<usize>> = (0..global_data.height)
+                .map(|row| {
+                    let coarse_row = ((row * coarse_height) / global_data.height).min(coarse_height - 1);
+                    (0..global_data.width)
+                        .map(|column| {
+                            let coarse_column = ((column * coarse_width) / global_data.width).min(
+                                coarse_width - 1
+                            );
+                            coarse_cluster_map[coarse_row][coarse_column]
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+            individual.mutation_parameters = coarse_individual.mutation_parameters;
+            individual.update_objectives(config, global_data);
+            individual
+        })
+        .collect()
+}
+
+/**
+ * Returns the pixels of the MST edges which are removed, ordered from the heaviest to the lightest edge. The edges are
+ * picked greedily by weight and an edge is skipped if removing it together with the already picked edges would leave
+ * a part with less pixels than the minimum segment size. So every prefix of the returned edges only creates parts
+ * with at least the minimum segment size.
+ */
+fn get_removable_mst_edges(
+    mst_edges: &[MstEdge],
+    number_of_pixels: usize,
+    minimum_segment_size: usize,
+    maximum_number_of_edges: usize
+) -> Vec<usize> {
+    if number_of_pixels == 0 {
+        return vec![];
+    }
+
+    // The edges are in the order Prim's algorithm added them, so every parent comes before its children
+    // and the subtree sizes can be summed up from the back
+    let mut subtree_sizes = vec![1; number_of_pixels];
+    for edge in mst_edges.iter().rev() {
+        subtree_sizes[edge.parent] += subtree_sizes[edge.pixel];
+    }
+
+    // Preorder of a depth first traversal. The subtree of a pixel are the pixels whose entry time is within
+    // entry time..entry time + subtree size of the pixel
+    let mut children = vec![vec![]; number_of_pixels];
+    let mut has_parent = vec![false; number_of_pixels];
+    for edge in mst_edges.iter() {
+        children[edge.parent].push(edge.pixel);
+        has_parent[edge.pixel] = true;
+    }
+    let root = (0..number_of_pixels).find(|pixel| !has_parent[*pixel]).unwrap();
+    let mut entry_times = vec![0; number_of_pixels];
+    let mut stack = vec![root];
+    let mut time = 0;
+    while let Some(pixel) = stack.pop() {
+        entry_times[pixel] = time;
+        time += 1;
+        stack.extend(children[pixel].iter());
+    }
+    let is_in_subtree = |pixel: usize, subtree_root: usize| {
+        entry_times[subtree_root] <= entry_times[pixel] &&
+            entry_times[pixel] < entry_times[subtree_root] + subtree_sizes[subtree_root]
+    };
+
+    let mut edges: Vec<&MstEdge> = mst_edges.iter().collect();
+    edges.sort_by(|a, b| b.weight.partial_cmp(&a.weight).unwrap());
+
+    // the root of every part, the size of the part and the index of the part the root was cut off from
+    let mut part_roots = vec![root];
+    let mut part_sizes = vec![number_of_pixels];
+    let mut parent_parts = vec![usize::MAX];
+    let mut removable_edges = vec![];
+    for edge in edges {
+        if removable_edges.len() >= maximum_number_of_edges {
+            break;
+        }
+        // the part containing the edge is the one with the deepest root above the pixel
+        let part = (0..part_roots.len())
+            .filter(|part| is_in_subtree(edge.pixel, part_roots[*part]))
+            .max_by_key(|part| entry_times[part_roots[*part]])
+            .unwrap();
+        let nested_parts: Vec<usize> = (0..part_roots.len())
+            .filter(|nested_part| {
+                parent_parts[*nested_part] == part && is_in_subtree(part_roots[*nested_part], edge.pixel)
+            })
+            .collect();
+        let size_below = subtree_sizes[edge.pixel] -
+            nested_parts
+                .iter()
+                .map(|nested_part| subtree_sizes[part_roots[*nested_part]])
+                .sum::<usize>();
+        let size_above = part_sizes[part] - size_below;
+        if size_below.min(size_above) < minimum_segment_size {
+            continue;
+        }
+
+        let new_part = part_roots.len();
+        part_roots.push(edge.pixel);
+        part_sizes.push(size_below);
+        parent_parts.push(part);
+        part_sizes[part] = size_above;
+        for nested_part in nested_parts {
+            parent_parts[nested_part] = new_part;
+        }
+        removable_edges.push(edge.pixel);
+    }
+    removable_edges
+}
+
 pub fn non_dominated_sort(population: &Population) -> Vec<Vec<Individual>> {
     let mut working_population = population.clone();
     let mut fronts: Vec<Vec<Individual>> = vec![];
@@ -92,29 +300,150 @@ pub fn clear_dir(dir: &Path) {
     }
 }
 
+/**
+ * Removes only the files and galleries a previous run exported to the directory, so a custom output directory can
+ * contain other files
+ */
+pub fn clear_exported_results(dir: &Path) {
+    if let Ok(entries) = read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() && file_name.starts_with("gallery_") {
+                clear_dir(&path);
+                remove_dir(path).unwrap();
+            } else if
+                path.is_file() &&
+                ((file_name.starts_with("result_") && file_name.ends_with(".png")) ||
+                    (file_name.starts_with("contact_sheet_") && file_name.ends_with(".png")) ||
+                    (file_name.starts_with("animation_") && file_name.ends_with(".gif")))
+            {
+                remove_file(path).unwrap();
+            }
+        }
+    }
+}
+
+/**
+ * Saves the segment border images of the individuals to the output directory and returns them
+ */
 pub fn save_individuals_to_files(
-    front: &Vec<Individual>,
+    front: &[Individual],
     config: &Config,
     global_data: &GlobalData
-) -> ImageResult<()> {
+) -> ImageResult<Vec<RgbImage>> {
     // Create the folder
-    let path_string = format!("./logs/result_segmentation/{}", config.problem_instance);
+    let path_string = get_output_directory(config);
 
     let path = Path::new(&path_string);
     create_dir_all(path).unwrap(); // Create the directory if it doesn't exist
 
     let mut thread_rng = rand::thread_rng();
+    let mut border_images = vec![];
     for individual in front {
         let border_image = individual.get_segment_border_image(global_data);
 
-        border_image.save(
-            format!(
-                "./logs/result_segmentation/{}/result_{}.png",
-                config.problem_instance,
-                thread_rng.gen::<u32>()
-            )
-        )?;
+        border_image.save(path.join(format!("result_{}.png", thread_rng.gen::<u32>())))?;
+        border_images.push(border_image);
     }
 
-    Ok(())
+    Ok(border_images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Sizes of the parts of the tree after removing the edges to the parents of the given pixels
+     */
+    fn get_part_sizes(mst_edges: &[MstEdge], number_of_pixels: usize, removed_pixels: &[usize]) -> Vec<usize> {
+        let mut parts: Vec<usize> = (0..number_of_pixels).collect();
+        fn find(parts: &mut Vec<usize>, pixel: usize) -> usize {
+            if parts[pixel] != pixel {
+                parts[pixel] = find(parts, parts[pixel]);
+            }
+            parts[pixel]
+        }
+        for edge in mst_edges.iter().filter(|edge| !removed_pixels.contains(&edge.pixel)) {
+            let part_a = find(&mut parts, edge.pixel);
+            let part_b = find(&mut parts, edge.parent);
+            parts[part_a] = part_b;
+        }
+        let mut part_sizes = vec![0; number_of_pixels];
+        for pixel in 0..number_of_pixels {
+            let part = find(&mut parts, pixel);
+            part_sizes[part] += 1;
+        }
+        part_sizes.into_iter().filter(|size| *size > 0).collect()
+    }
+
+    /**
+     * A tree of 12 pixels rooted in 0 with the branches 0-1-2-3-4, 1-5-6-7 and 0-8-9-10-11, in the order of Prim's
+     * algorithm
+     */
+    fn get_test_mst_edges() -> Vec<MstEdge> {
+        [
+            (1, 0, 1.0),
+            (2, 1, 9.0),
+            (3, 2, 2.0),
+            (4, 3, 8.0),
+            (5, 1, 7.0),
+            (6, 5, 3.0),
+            (7, 6, 6.0),
+            (8, 0, 5.0),
+            (9, 8, 10.0),
+            (10, 9, 1.5),
+            (11, 10, 4.0),
+        ]
+            .iter()
+            .map(|(pixel, parent, weight)| MstEdge { pixel: *pixel, parent: *parent, weight: *weight })
+            .collect()
+    }
+
+    #[test]
+    fn every_prefix_of_the_removable_edges_keeps_the_minimum_segment_size() {
+        let mst_edges = get_test_mst_edges();
+        for minimum_segment_size in 1..=6 {
+            let removable_edges = get_removable_mst_edges(&mst_edges, 12, minimum_segment_size, usize::MAX);
+            for length in 0..=removable_edges.len() {
+                let part_sizes = get_part_sizes(&mst_edges, 12, &removable_edges[..length]);
+                assert_eq!(part_sizes.len(), length + 1);
+                assert!(
+                    part_sizes.iter().all(|size| *size >= minimum_segment_size),
+                    "minimum segment size {} prefix {:?} parts {:?}",
+                    minimum_segment_size,
+                    &removable_edges[..length],
+                    part_sizes
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn removable_edges_are_the_heaviest_allowed_edges() {
+        let mst_edges = get_test_mst_edges();
+        // every edge can be removed without a minimum segment size
+        assert_eq!(get_removable_mst_edges(&mst_edges, 12, 1, usize::MAX), vec![9, 2, 4, 5, 7, 8, 11, 6, 3, 10, 1]);
+        // after cutting off 9-10-11, 2-3-4 and 5-6-7 every other edge would leave a single pixel
+        assert_eq!(get_removable_mst_edges(&mst_edges, 12, 2, usize::MAX), vec![9, 2, 5]);
+        // only the edge above 1 splits the tree into 7 and 5 pixels
+        assert_eq!(get_removable_mst_edges(&mst_edges, 12, 5, usize::MAX), vec![1]);
+        assert!(get_removable_mst_edges(&mst_edges, 12, 6, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn removable_edges_respect_the_maximum_number_of_edges() {
+        let mst_edges = get_test_mst_edges();
+        for maximum_number_of_edges in 0..12 {
+            let removable_edges = get_removable_mst_edges(&mst_edges, 12, 1, maximum_number_of_edges);
+            assert_eq!(removable_edges.len(), maximum_number_of_edges);
+        }
+        assert_eq!(get_removable_mst_edges(&mst_edges, 12, 2, 2), vec![9, 2]);
+    }
+
+    #[test]
+    fn removable_edges_of_an_empty_image_are_empty() {
+        assert!(get_removable_mst_edges(&[], 0, 1, 10).is_empty());
+    }
 }
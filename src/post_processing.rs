@@ -0,0 +1,118 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    config::Config,
+    distance::color_distance,
+    global_data::GlobalData,
+    individual::Individual,
+    region_adjacency_graph::RegionAdjacencyGraph,
+};
+
+/**
+ * Merges all segments with less pixels than the minimum segment size into the adjacent segment with the most
+ * similar mean color. The merge is deterministic: always the smallest segment is merged next and ties are broken
+ * by the segment id. The genome of the merged segment is rebuilt as a tree which is rooted in the neighboring
+ * segment, so the result stays a valid individual. Returns the number of merged segments.
+ */
+pub fn merge_small_segments(
+    individual: &mut Individual,
+    minimum_segment_size: usize,
+    config: &Config,
+    global_data: &GlobalData
+) -> usize {
+    let mut region_adjacency_graph = RegionAdjacencyGraph::from_individual(individual, global_data);
+    let mut number_of_merged_segments = 0;
+
+    // (size, region) of all regions which are too small, ordered from the smallest to the biggest
+    let mut small_regions: BTreeSet<(usize, usize)> = region_adjacency_graph.nodes
+        .iter()
+        .filter(|(_, node)| node.size < minimum_segment_size)
+        .map(|(region, node)| (node.size, *region))
+        .collect();
+
+    while region_adjacency_graph.nodes.len() > 1 {
+        let (_, smallest_region) = match small_regions.pop_first() {
+            Some(entry) => entry,
+            None => {
+                break;
+            }
+        };
+
+        let mean_color = region_adjacency_graph.nodes.get(&smallest_region).unwrap().mean_color;
+        let most_similar_neighbor = region_adjacency_graph
+            .get_neighbors(smallest_region)
+            .into_iter()
+            .map(|neighbor| {
+                let distance = color_distance(
+                    &mean_color,
+                    &region_adjacency_graph.nodes.get(&neighbor).unwrap().mean_color,
+                    global_data.distance_metric
+                );
+                (neighbor, distance)
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then(a.0.cmp(&b.0)))
+            .map(|(neighbor, _)| neighbor)
+            .unwrap();
+
+        let neighbor_size = region_adjacency_graph.nodes.get(&most_similar_neighbor).unwrap().size;
+        region_adjacency_graph.merge_regions(individual, smallest_region, most_similar_neighbor);
+        number_of_merged_segments += 1;
+
+        // the neighbor grew, so its position in the ordered set changes
+        if small_regions.remove(&(neighbor_size, most_similar_neighbor)) {
+            let new_size = region_adjacency_graph.nodes.get(&most_similar_neighbor).unwrap().size;
+            if new_size < minimum_segment_size {
+                small_regions.insert((new_size, most_similar_neighbor));
+            }
+        }
+    }
+
+    individual.set_needs_update();
+    individual.update_objectives(config, global_data);
+    number_of_merged_segments
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ Rgb, RgbImage };
+
+    use super::*;
+    use crate::{ config::get_test_config, global_data::ImageData, individual::get_genome_from_label_map };
+
+    #[test]
+    fn merge_small_segments_merges_into_the_most_similar_neighbor() {
+        // a red left half and a blue right half. The small segments touch both halves and have a similar color to one
+        let label_map = vec![
+            vec![1, 1, 1, 2, 2, 2],
+            vec![1, 1, 3, 2, 2, 2],
+            vec![1, 1, 1, 4, 4, 2],
+            vec![1, 1, 1, 2, 2, 2]
+        ];
+        let rgb_image = RgbImage::from_fn(6, 4, |column, row| {
+            match label_map[row as usize][column as usize] {
+                1 => Rgb([200, 20, 20]),
+                2 => Rgb([20, 20, 200]),
+                3 => Rgb([180, 40, 30]),
+                _ => Rgb([40, 30, 180]),
+            }
+        });
+        let config = get_test_config();
+        let image_data = ImageData::new(rgb_image, &config, None);
+        let global_data = image_data.get_global_data();
+        let mut individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+
+        assert_eq!(merge_small_segments(&mut individual, 3, &config, &global_data), 2);
+
+        assert!(individual.validate(6, 4).is_valid());
+        let cluster_map = individual.get_cluster_map(6, 4);
+        let left_segment = cluster_map[0][0];
+        let right_segment = cluster_map[0][5];
+        assert_ne!(left_segment, right_segment);
+        for (row, labels) in label_map.iter().enumerate() {
+            for (column, label) in labels.iter().enumerate() {
+                let expected_segment = if *label % 2 == 1 { left_segment } else { right_segment };
+                assert_eq!(cluster_map[row][column], expected_segment, "pixel ({}, {})", row, column);
+            }
+        }
+    }
+}
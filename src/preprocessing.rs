@@ -0,0 +1,188 @@
+use std::{ fs::create_dir_all, path::{ Path, PathBuf } };
+
+use image::{ GrayImage, Luma, RgbImage };
+use imageproc::{
+    distance_transform::Norm,
+    edges::canny,
+    filter::{ gaussian_blur_f32, median_filter },
+    morphology::{ close, dilate, erode, open },
+};
+
+use crate::config::{ Config, PreprocessingStepConfig };
+
+/**
+ * The pipeline which was used before it became configurable: canny, blur, offset and normalization
+ */
+pub fn get_default_edge_steps(config: &Config) -> Vec<PreprocessingStepConfig> {
+    let step = |name: &str| PreprocessingStepConfig {
+        name: name.to_string(),
+        sigma: None,
+        low: None,
+        high: None,
+        radius: None,
+        norm: None,
+        offset: None,
+    };
+    vec![
+        PreprocessingStepConfig {
+            low: Some(config.canny_hysteresis_low),
+            high: Some(config.canny_hysteresis_high),
+            ..step("canny")
+        },
+        // Blur the image so we have a bit of a buffer around the edges
+        PreprocessingStepConfig { sigma: Some(config.blur_sigma), ..step("gaussian_blur") },
+        // add delta to each pixel so we dont loose mutations at spots which are not edges
+        PreprocessingStepConfig { offset: Some(10), ..step("offset") },
+        step("normalize")
+    ]
+}
+
+/**
+ * Returns the parameter of the step or panics with the name of the step if it is missing in the config
+ */
+fn get_parameter<T: Copy>(value: Option<T>, parameter: &str, step: &PreprocessingStepConfig) -> T {
+    value.unwrap_or_else(|| panic!("Missing {} for preprocessing step {:?}", parameter, step.name.as_str()))
+}
+
+/**
+ * The morphological operations of imageproc take the radius as u8
+ */
+fn get_morphology_radius(step: &PreprocessingStepConfig) -> u8 {
+    let radius = get_parameter(step.radius, "radius", step);
+    u8::try_from(radius).unwrap_or_else(|_| {
+        panic!("The radius {} of preprocessing step {:?} has to be below 256", radius, step.name.as_str())
+    })
+}
+
+fn get_norm(step: &PreprocessingStepConfig) -> Norm {
+    match step.norm.as_deref().unwrap_or("linf") {
+        "l1" => Norm::L1,
+        "linf" => Norm::LInf,
+        _ => panic!("Didn't have an Implementation for norm: {:?}", step.norm),
+    }
+}
+
+/**
+ * Calculates the gradient magnitude with a 3x3 derivative kernel whose smoothing weights are (side, center, side).
+ * Sobel uses (1, 2, 1) and Scharr (3, 10, 3). The result is scaled to 0 to 255.
+ */
+fn get_gradient_magnitude_image(gray_image: &GrayImage, side: f64, center: f64) -> GrayImage {
+    let (width, height) = (gray_image.width() as i64, gray_image.height() as i64);
+    let intensity = |column: i64, row: i64| {
+        gray_image.get_pixel(
+            column.clamp(0, width - 1) as u32,
+            row.clamp(0, height - 1) as u32
+        )[0] as f64
+    };
+
+    let mut magnitudes = vec![0.0; (width * height) as usize];
+    for row in 0..height {
+        for column in 0..width {
+            let horizontal =
+                side * (intensity(column + 1, row - 1) - intensity(column - 1, row - 1)) +
+                center * (intensity(column + 1, row) - intensity(column - 1, row)) +
+                side * (intensity(column + 1, row + 1) - intensity(column - 1, row + 1));
+            let vertical =
+                side * (intensity(column - 1, row + 1) - intensity(column - 1, row - 1)) +
+                center * (intensity(column, row + 1) - intensity(column, row - 1)) +
+                side * (intensity(column + 1, row + 1) - intensity(column + 1, row - 1));
+            magnitudes[(row * width + column) as usize] = horizontal.hypot(vertical);
+        }
+    }
+
+    let maximum = magnitudes.iter().cloned().fold(0.0, f64::max);
+    let scale = if maximum > 0.0 { 255.0 / maximum } else { 0.0 };
+    GrayImage::from_fn(width as u32, height as u32, |column, row| {
+        Luma([(magnitudes[(row as i64 * width + column as i64) as usize] * scale).round() as u8])
+    })
+}
+
+/**
+ * Returns the path an intermediate image is saved to, or None if the images are not exported
+ */
+fn get_export_path(export_directory: Option<&Path>, file_name: &str) -> Option<PathBuf> {
+    export_directory.map(|export_directory| {
+        create_dir_all(export_directory).unwrap();
+        export_directory.join(file_name)
+    })
+}
+
+/**
+ * Applies the steps to the rgb image, e.g. to denoise it before the colors are used in the objectives.
+ * Supports "gaussian_blur" (sigma) and "median" (radius).
+ */
+pub fn run_rgb_pipeline(
+    rgb_image: &RgbImage,
+    steps: &[PreprocessingStepConfig],
+    export_directory: Option<&Path>
+) -> RgbImage {
+    let mut image = rgb_image.clone();
+    for (index, step) in steps.iter().enumerate() {
+        image = match step.name.as_str() {
+            "gaussian_blur" => gaussian_blur_f32(&image, get_parameter(step.sigma, "sigma", step)),
+            "median" => {
+                let radius = get_parameter(step.radius, "radius", step);
+                median_filter(&image, radius, radius)
+            }
+            _ => panic!("Didn't have an Implementation for rgb preprocessing step: {:?}", step.name.as_str()),
+        };
+        if let Some(path) = get_export_path(export_directory, &format!("rgb_{}_{}.png", index, step.name)) {
+            image.save(path).unwrap();
+        }
+    }
+    image
+}
+
+/**
+ * Applies the steps to the grayscale image to create the map the mutation weights are calculated from.
+ * Supports "gaussian_blur" (sigma), "median" (radius), "canny" (low, high), "sobel", "scharr",
+ * "dilate", "erode", "open", "close" (radius, norm), "offset" (offset) and "normalize".
+ * The morphological operations are binary, every pixel which is not 0 counts as foreground. So they are meant for the
+ * output of canny.
+ */
+pub fn run_edge_pipeline(
+    gray_image: &GrayImage,
+    steps: &[PreprocessingStepConfig],
+    export_directory: Option<&Path>
+) -> GrayImage {
+    let mut image = gray_image.clone();
+    for (index, step) in steps.iter().enumerate() {
+        image = match step.name.as_str() {
+            "gaussian_blur" => gaussian_blur_f32(&image, get_parameter(step.sigma, "sigma", step)),
+            "median" => {
+                let radius = get_parameter(step.radius, "radius", step);
+                median_filter(&image, radius, radius)
+            }
+            "canny" => canny(&image, get_parameter(step.low, "low", step), get_parameter(step.high, "high", step)),
+            "sobel" => get_gradient_magnitude_image(&image, 1.0, 2.0),
+            "scharr" => get_gradient_magnitude_image(&image, 3.0, 10.0),
+            "dilate" => dilate(&image, get_norm(step), get_morphology_radius(step)),
+            "erode" => erode(&image, get_norm(step), get_morphology_radius(step)),
+            "open" => open(&image, get_norm(step), get_morphology_radius(step)),
+            "close" => close(&image, get_norm(step), get_morphology_radius(step)),
+            "offset" => {
+                let offset = get_parameter(step.offset, "offset", step);
+                image.pixels_mut().for_each(|pixel| {
+                    *pixel = Luma([pixel.0[0].saturating_add(offset)]);
+                });
+                image
+            }
+            "normalize" => {
+                // Scale the image between 0 and 255
+                let max_val = image.iter().cloned().max().unwrap();
+                if max_val > 0 {
+                    let scale = 255.0 / (max_val as f32);
+                    image.pixels_mut().for_each(|pixel| {
+                        *pixel = Luma([((pixel.0[0] as f32) * scale).round() as u8]);
+                    });
+                }
+                image
+            }
+            _ => panic!("Didn't have an Implementation for edge preprocessing step: {:?}", step.name.as_str()),
+        };
+        if let Some(path) = get_export_path(export_directory, &format!("edge_{}_{}.png", index, step.name)) {
+            image.save(path).unwrap();
+        }
+    }
+    image
+}
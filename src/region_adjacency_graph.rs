@@ -0,0 +1,367 @@
+use std::collections::{ BTreeMap, BTreeSet, VecDeque };
+
+use crate::{
+    global_data::GlobalData,
+    individual::{ build_spanning_tree, get_connection_to_neighbor, Connection, Individual },
+};
+
+#[derive(Debug, Clone)]
+pub struct RegionNode {
+    pub size: usize,
+    // (row, column)
+    pub centroid: (f64, f64),
+    pub mean_color: (f64, f64, f64),
+    pub color_variance: (f64, f64, f64),
+    pub pixels: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegionEdge {
+    // number of horizontally or vertically neighboring pixel pairs between the two regions
+    pub shared_border_length: usize,
+    // mean color distance of the neighboring pixel pairs between the two regions
+    pub mean_boundary_gradient: f64,
+    // the first pixel pair (in reading order) between the regions. The first pixel belongs to the region with the smaller id
+    pub connecting_pixels: (usize, usize),
+}
+
+/**
+ * Graph of the segments of an individual. Every segment is a node and segments sharing a border are connected by an edge.
+ * Edges are stored with the smaller region id first.
+ */
+#[derive(Debug, Clone)]
+pub struct RegionAdjacencyGraph {
+    pub cluster_map: Vec<Vec<usize>>,
+    pub nodes: BTreeMap<usize, RegionNode>,
+    pub edges: BTreeMap<(usize, usize), RegionEdge>,
+    // the neighboring regions of every region
+    adjacency: BTreeMap<usize, BTreeSet<usize>>,
+    width: usize,
+    height: usize,
+}
+
+fn get_edge_key(region_a: usize, region_b: usize) -> (usize, usize) {
+    if region_a < region_b { (region_a, region_b) } else { (region_b, region_a) }
+}
+
+impl RegionAdjacencyGraph {
+    pub fn from_individual(individual: &Individual, global_data: &GlobalData) -> RegionAdjacencyGraph {
+        let width = global_data.width;
+        let height = global_data.height;
+        let cluster_map = individual.get_cluster_map(width as i64, height as i64);
+
+        // Aggregate the sums for the node statistics
+        let mut nodes: BTreeMap<usize, RegionNode> = BTreeMap::new();
+        let mut squared_color_sums: BTreeMap<usize, (f64, f64, f64)> = BTreeMap::new();
+        for (row, regions) in cluster_map.iter().enumerate() {
+            for (column, &region) in regions.iter().enumerate() {
+                let pixel = global_data.color_map[row][column];
+                let node = nodes.entry(region).or_insert(RegionNode {
+                    size: 0,
+                    centroid: (0.0, 0.0),
+                    mean_color: (0.0, 0.0, 0.0),
+                    color_variance: (0.0, 0.0, 0.0),
+                    pixels: vec![],
+                });
+                node.size += 1;
+                node.centroid.0 += row as f64;
+                node.centroid.1 += column as f64;
+                node.mean_color.0 += pixel.0;
+                node.mean_color.1 += pixel.1;
+                node.mean_color.2 += pixel.2;
+                node.pixels.push(row * width + column);
+
+                let squared_color_sum = squared_color_sums.entry(region).or_insert((0.0, 0.0, 0.0));
+                squared_color_sum.0 += pixel.0.powi(2);
+                squared_color_sum.1 += pixel.1.powi(2);
+                squared_color_sum.2 += pixel.2.powi(2);
+            }
+        }
+        for (region, node) in nodes.iter_mut() {
+            let size = node.size as f64;
+            node.centroid = (node.centroid.0 / size, node.centroid.1 / size);
+            node.mean_color = (
+                node.mean_color.0 / size,
+                node.mean_color.1 / size,
+                node.mean_color.2 / size,
+            );
+            let squared_color_sum = squared_color_sums.get(region).unwrap();
+            node.color_variance = (
+                (squared_color_sum.0 / size - node.mean_color.0.powi(2)).max(0.0),
+                (squared_color_sum.1 / size - node.mean_color.1.powi(2)).max(0.0),
+                (squared_color_sum.2 / size - node.mean_color.2.powi(2)).max(0.0),
+            );
+        }
+
+        // Only the right and lower neighbor is checked, so every pixel pair is counted once
+        let mut edges: BTreeMap<(usize, usize), RegionEdge> = BTreeMap::new();
+        for row in 0..height {
+            for column in 0..width {
+                for (row_adjustment, column_adjustment) in [
+                    (0, 1),
+                    (1, 0),
+                ] {
+                    let new_row = row + row_adjustment;
+                    let new_column = column + column_adjustment;
+                    if new_row >= height || new_column >= width {
+                        continue;
+                    }
+                    let region = cluster_map[row][column];
+                    let neighbor_region = cluster_map[new_row][new_column];
+                    if region == neighbor_region {
+                        continue;
+                    }
+
+                    let index = row * width + column;
+                    let neighbor_index = new_row * width + new_column;
+                    let gradient =
+                        global_data.distance_map[row][column][3 + row_adjustment]
+                            [3 + column_adjustment];
+                    let edge = edges.entry(get_edge_key(region, neighbor_region)).or_insert(RegionEdge {
+                        shared_border_length: 0,
+                        mean_boundary_gradient: 0.0,
+                        connecting_pixels: if region < neighbor_region {
+                            (index, neighbor_index)
+                        } else {
+                            (neighbor_index, index)
+                        },
+                    });
+                    edge.shared_border_length += 1;
+                    // the sum is divided by the border length afterwards
+                    edge.mean_boundary_gradient += gradient;
+                }
+            }
+        }
+        let mut adjacency: BTreeMap<usize, BTreeSet<usize>> = nodes
+            .keys()
+            .map(|region| (*region, BTreeSet::new()))
+            .collect();
+        for ((region_a, region_b), edge) in edges.iter_mut() {
+            edge.mean_boundary_gradient /= edge.shared_border_length as f64;
+            adjacency.get_mut(region_a).unwrap().insert(*region_b);
+            adjacency.get_mut(region_b).unwrap().insert(*region_a);
+        }
+
+        RegionAdjacencyGraph {
+            cluster_map,
+            nodes,
+            edges,
+            adjacency,
+            width,
+            height,
+        }
+    }
+
+    pub fn get_region_of_pixel(&self, index: usize) -> usize {
+        self.cluster_map[index / self.width][index % self.width]
+    }
+
+    pub fn get_edge(&self, region_a: usize, region_b: usize) -> Option<&RegionEdge> {
+        self.edges.get(&get_edge_key(region_a, region_b))
+    }
+
+    pub fn get_neighbors(&self, region: usize) -> Vec<usize> {
+        self.adjacency.get(&region).unwrap().iter().copied().collect()
+    }
+
+    /**
+     * Merges the source region into the adjacent target region. The genome of the source region is rebuilt as a
+     * tree which is rooted in the target region, the graph is updated accordingly.
+     */
+    pub fn merge_regions(&mut self, individual: &mut Individual, source: usize, target: usize) {
+        let edge = self
+            .get_edge(source, target)
+            .unwrap_or_else(|| panic!("Region {} and {} are not adjacent", source, target))
+            .clone();
+        let (source_pixel, target_pixel) = if source < target {
+            edge.connecting_pixels
+        } else {
+            (edge.connecting_pixels.1, edge.connecting_pixels.0)
+        };
+
+        let cluster_map = &self.cluster_map;
+        let width = self.width;
+        build_spanning_tree(
+            &mut individual.genome,
+            source_pixel,
+            get_connection_to_neighbor(source_pixel, target_pixel, width),
+            width,
+            self.height,
+            |index| cluster_map[index / width][index % width] == source
+        );
+        individual.set_needs_update();
+
+        // Update the node statistics with the combined values of both regions
+        let source_node = self.nodes.remove(&source).unwrap();
+        for pixel in source_node.pixels.iter() {
+            self.cluster_map[pixel / width][pixel % width] = target;
+        }
+        let target_node = self.nodes.get_mut(&target).unwrap();
+        let source_weight = (source_node.size as f64) / ((source_node.size + target_node.size) as f64);
+        let target_weight = 1.0 - source_weight;
+        let combine = |source_value: f64, target_value: f64| {
+            source_weight * source_value + target_weight * target_value
+        };
+        let mean_color = (
+            combine(source_node.mean_color.0, target_node.mean_color.0),
+            combine(source_node.mean_color.1, target_node.mean_color.1),
+            combine(source_node.mean_color.2, target_node.mean_color.2),
+        );
+        // variance of the union = weighted mean of the variances + weighted squared distance of the means
+        target_node.color_variance = (
+            combine(
+                source_node.color_variance.0 + (source_node.mean_color.0 - mean_color.0).powi(2),
+                target_node.color_variance.0 + (target_node.mean_color.0 - mean_color.0).powi(2)
+            ),
+            combine(
+                source_node.color_variance.1 + (source_node.mean_color.1 - mean_color.1).powi(2),
+                target_node.color_variance.1 + (target_node.mean_color.1 - mean_color.1).powi(2)
+            ),
+            combine(
+                source_node.color_variance.2 + (source_node.mean_color.2 - mean_color.2).powi(2),
+                target_node.color_variance.2 + (target_node.mean_color.2 - mean_color.2).powi(2)
+            ),
+        );
+        target_node.mean_color = mean_color;
+        target_node.centroid = (
+            combine(source_node.centroid.0, target_node.centroid.0),
+            combine(source_node.centroid.1, target_node.centroid.1),
+        );
+        target_node.size += source_node.size;
+        target_node.pixels.extend(source_node.pixels);
+
+        // Move the edges of the source region to the target region
+        self.edges.remove(&get_edge_key(source, target));
+        let source_neighbors = self.adjacency.remove(&source).unwrap();
+        self.adjacency.get_mut(&target).unwrap().remove(&source);
+        for other in source_neighbors {
+            if other == target {
+                continue;
+            }
+            let key = get_edge_key(source, other);
+            let source_edge = self.edges.remove(&key).unwrap();
+            let other_neighbors = self.adjacency.get_mut(&other).unwrap();
+            other_neighbors.remove(&source);
+            other_neighbors.insert(target);
+            self.adjacency.get_mut(&target).unwrap().insert(other);
+            // the connecting pixel of the source region now belongs to the target region
+            let (source_side_pixel, other_side_pixel) = if key.0 == source {
+                source_edge.connecting_pixels
+            } else {
+                (source_edge.connecting_pixels.1, source_edge.connecting_pixels.0)
+            };
+            let connecting_pixels = if target < other {
+                (source_side_pixel, other_side_pixel)
+            } else {
+                (other_side_pixel, source_side_pixel)
+            };
+
+            match self.edges.get_mut(&get_edge_key(target, other)) {
+                Some(target_edge) => {
+                    let border_length =
+                        target_edge.shared_border_length + source_edge.shared_border_length;
+                    target_edge.mean_boundary_gradient =
+                        (target_edge.mean_boundary_gradient *
+                            (target_edge.shared_border_length as f64) +
+                            source_edge.mean_boundary_gradient *
+                                (source_edge.shared_border_length as f64)) /
+                        (border_length as f64);
+                    target_edge.shared_border_length = border_length;
+                }
+                None => {
+                    self.edges.insert(get_edge_key(target, other), RegionEdge {
+                        connecting_pixels,
+                        ..source_edge
+                    });
+                }
+            }
+        }
+    }
+
+    /**
+     * Splits a region into two regions which are grown from the two seed pixels. Pixels for which is_barrier returns
+     * true are only assigned after all other pixels of the region, so the split follows the barrier.
+     * The genome of both parts is rebuilt as a tree rooted in its seed and the graph is rebuilt.
+     */
+    pub fn split_region(
+        &mut self,
+        individual: &mut Individual,
+        global_data: &GlobalData,
+        region: usize,
+        first_seed: usize,
+        second_seed: usize,
+        is_barrier: impl Fn(usize) -> bool
+    ) {
+        if
+            first_seed == second_seed ||
+            self.get_region_of_pixel(first_seed) != region ||
+            self.get_region_of_pixel(second_seed) != region
+        {
+            panic!("Both seeds need to be different pixels of region {}", region);
+        }
+
+        // 0 = not part of the region, 1 = unassigned, 2 = first part, 3 = second part
+        let mut labels: Vec<u8> = vec![0; self.width * self.height];
+        for pixel in self.nodes.get(&region).unwrap().pixels.iter() {
+            labels[*pixel] = 1;
+        }
+        labels[first_seed] = 2;
+        labels[second_seed] = 3;
+
+        // Grow both parts at the same pace, first without and then with the barrier pixels
+        let mut queue: VecDeque<usize> = VecDeque::from(vec![first_seed, second_seed]);
+        for allow_barrier in [false, true] {
+            if allow_barrier {
+                queue.extend(
+                    self.nodes
+                        .get(&region)
+                        .unwrap()
+                        .pixels.iter()
+                        .filter(|pixel| labels[**pixel] > 1)
+                );
+            }
+            while let Some(index) = queue.pop_front() {
+                let column = index % self.width;
+                let row = index / self.width;
+                for (row_adjustment, column_adjustment) in [
+                    (-1, 0),
+                    (1, 0),
+                    (0, -1),
+                    (0, 1),
+                ] {
+                    let new_row = ((row as isize) + row_adjustment) as usize;
+                    let new_column = ((column as isize) + column_adjustment) as usize;
+                    if new_row >= self.height || new_column >= self.width {
+                        continue;
+                    }
+                    let new_index = new_row * self.width + new_column;
+                    if labels[new_index] != 1 {
+                        continue;
+                    }
+                    if !allow_barrier && is_barrier(new_index) {
+                        continue;
+                    }
+                    labels[new_index] = labels[index];
+                    queue.push_back(new_index);
+                }
+            }
+        }
+
+        for (seed, label) in [
+            (first_seed, 2),
+            (second_seed, 3),
+        ] {
+            build_spanning_tree(
+                &mut individual.genome,
+                seed,
+                Connection::None,
+                self.width,
+                self.height,
+                |index| labels[index] == label
+            );
+        }
+        individual.set_needs_update();
+
+        *self = RegionAdjacencyGraph::from_individual(individual, global_data);
+    }
+}
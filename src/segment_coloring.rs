@@ -0,0 +1,124 @@
+use image::{ Rgb, RgbImage };
+use rand::{ rngs::StdRng, Rng, SeedableRng };
+
+use crate::region_adjacency_graph::RegionAdjacencyGraph;
+
+// the colors of the segments image, all of them are distinct
+const PALETTE: [(u8, u8, u8); 9] = [
+    (25, 200, 56),
+    (138, 42, 226),
+    (158, 72, 0),
+    (241, 76, 192),
+    (163, 163, 163),
+    (255, 195, 0),
+    (0, 214, 255),
+    (1, 62, 255),
+    (255, 123, 0),
+];
+
+/**
+ * Converts a hue in degrees, a saturation and a value between 0 and 1 to rgb
+ */
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Rgb<u8> {
+    let chroma = value * saturation;
+    let sector = (hue / 60.0).rem_euclid(6.0);
+    let second = chroma * (1.0 - ((sector % 2.0) - 1.0).abs());
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let offset = value - chroma;
+    let to_channel = |channel: f64| ((channel + offset) * 255.0).round() as u8;
+    Rgb([to_channel(red), to_channel(green), to_channel(blue)])
+}
+
+/**
+ * Colors indexed by the segment id, cycling through the palette
+ */
+pub fn get_palette_colors(number_of_segments: usize) -> Vec<Rgb<u8>> {
+    (0..=number_of_segments)
+        .map(|segment| {
+            let color = PALETTE[segment % PALETTE.len()];
+            Rgb([color.0, color.1, color.2])
+        })
+        .collect()
+}
+
+/**
+ * Random bright colors indexed by the segment id. The same seed always gives the same colors, so the segment ids of
+ * different individuals are comparable.
+ */
+pub fn get_random_hsv_colors(number_of_segments: usize, seed: u64) -> Vec<Rgb<u8>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..=number_of_segments)
+        .map(|_| hsv_to_rgb(rng.gen_range(0.0..360.0), rng.gen_range(0.5..1.0), rng.gen_range(0.7..1.0)))
+        .collect()
+}
+
+/**
+ * The mean rgb color of every segment, indexed by the segment id
+ */
+pub fn get_mean_colors(cluster_map: &[Vec<usize>], rgb_image: &RgbImage, number_of_segments: usize) -> Vec<Rgb<u8>> {
+    let mut sums = vec![(0.0, 0.0, 0.0, 0); number_of_segments + 1];
+    for (row, segments) in cluster_map.iter().enumerate() {
+        for (column, segment) in segments.iter().enumerate() {
+            let pixel = rgb_image.get_pixel(column as u32, row as u32);
+            let sum = &mut sums[*segment];
+            sum.0 += pixel[0] as f64;
+            sum.1 += pixel[1] as f64;
+            sum.2 += pixel[2] as f64;
+            sum.3 += 1;
+        }
+    }
+    sums.iter()
+        .map(|sum| {
+            let count = sum.3.max(1) as f64;
+            Rgb([(sum.0 / count).round() as u8, (sum.1 / count).round() as u8, (sum.2 / count).round() as u8])
+        })
+        .collect()
+}
+
+/**
+ * Greedy graph coloring of the region adjacency graph, so adjacent segments never share a color. The segments with
+ * the most neighbors are colored first and take the first palette color none of their neighbors has. If the palette
+ * is not enough, additional seeded random colors are appended.
+ */
+pub fn get_graph_colors(
+    region_adjacency_graph: &RegionAdjacencyGraph,
+    number_of_segments: usize,
+    seed: u64
+) -> Vec<Rgb<u8>> {
+    let mut segments: Vec<usize> = region_adjacency_graph.nodes.keys().copied().collect();
+    let mut neighbors = vec![vec![]; number_of_segments + 1];
+    for segment in segments.iter() {
+        neighbors[*segment] = region_adjacency_graph.get_neighbors(*segment);
+    }
+    segments.sort_by(|a, b| neighbors[*b].len().cmp(&neighbors[*a].len()).then(a.cmp(b)));
+
+    let mut color_indices: Vec<Option<usize>> = vec![None; number_of_segments + 1];
+    let mut number_of_colors = PALETTE.len();
+    for segment in segments {
+        let mut used = vec![false; neighbors[segment].len() + 1];
+        for neighbor in neighbors[segment].iter() {
+            if let Some(color_index) = color_indices[*neighbor] {
+                if color_index < used.len() {
+                    used[color_index] = true;
+                }
+            }
+        }
+        let color_index = used.iter().position(|is_used| !is_used).unwrap();
+        number_of_colors = number_of_colors.max(color_index + 1);
+        color_indices[segment] = Some(color_index);
+    }
+
+    let mut colors = get_palette_colors(PALETTE.len() - 1);
+    colors.extend(get_random_hsv_colors(number_of_colors - PALETTE.len(), seed).into_iter().skip(1));
+    color_indices
+        .iter()
+        .map(|color_index| colors[color_index.unwrap_or(0)])
+        .collect()
+}
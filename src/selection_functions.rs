@@ -59,80 +59,39 @@ fn nsga_2_selection(population: &Population, population_size: usize) -> Populati
             .enumerate()
             .map(|(index, individual)| (index, individual.clone(), 0.0))
             .collect();
-        // calculate distance based on edge value fitness
-        sortable_individuals.sort_by(|a, b|
-            a.1.get_objectives().0.partial_cmp(&b.1.get_objectives().0).unwrap()
-        );
+        let number_of_objectives = sortable_individuals[0].1.get_objectives().len();
+        for objective_index in 0..number_of_objectives {
+            // calculate distance based on the current objective
+            sortable_individuals.sort_by(|a, b|
+                a.1.get_objectives()[objective_index]
+                    .partial_cmp(&b.1.get_objectives()[objective_index])
+                    .unwrap()
+            );
 
-        // get minimum and maximum edge value fitness
-        let max_edge_value_fitness = sorted_population[0]
-            .iter()
-            .max_by(|a, b| a.get_objectives().0.partial_cmp(&b.get_objectives().0).unwrap())
-            .unwrap()
-            .get_objectives().0;
-        let min_edge_value_fitness = sorted_population[sorted_population.len() - 1]
-            .iter()
-            .min_by(|a, b| a.get_objectives().0.partial_cmp(&b.get_objectives().0).unwrap())
-            .unwrap()
-            .get_objectives().0;
-        // assign the distance to the first and last individual
-        sortable_individuals[0].2 = f64::INFINITY;
-        sortable_individuals[sorted_population[i].len() - 1].2 = f64::INFINITY;
-        for j in 1..sortable_individuals.len() - 1 {
-            sortable_individuals[j].2 +=
-                (sortable_individuals[j + 1].1.get_objectives().0 -
-                    sortable_individuals[j - 1].1.get_objectives().0) /
-                (max_edge_value_fitness - min_edge_value_fitness);
-        }
-
-        // calculate distance based on connectivity fitness
-        sortable_individuals.sort_by(|a, b|
-            a.1.get_objectives().1.partial_cmp(&b.1.get_objectives().1).unwrap()
-        );
-        // get minimum and maximum connectivity fitness
-        let max_connectivity_fitness = sorted_population[sorted_population.len() - 1]
-            .iter()
-            .max_by(|a, b| a.get_objectives().1.partial_cmp(&b.get_objectives().1).unwrap())
-            .unwrap()
-            .get_objectives().1;
-        let min_connectivity_fitness = sorted_population[0]
-            .iter()
-            .min_by(|a, b| a.get_objectives().1.partial_cmp(&b.get_objectives().1).unwrap())
-            .unwrap()
-            .get_objectives().1;
-        // assign the distance to the first and last individual
-        sortable_individuals[0].2 = f64::INFINITY;
-        sortable_individuals[sorted_population[i].len() - 1].2 = f64::INFINITY;
-        for j in 1..sortable_individuals.len() - 1 {
-            sortable_individuals[j].2 +=
-                (sortable_individuals[j + 1].1.get_objectives().1 -
-                    sortable_individuals[j - 1].1.get_objectives().1) /
-                (max_connectivity_fitness - min_connectivity_fitness);
-        }
+            // get minimum and maximum value of the objective over all fronts
+            let max_objective_value = sorted_population
+                .iter()
+                .flatten()
+                .map(|individual| individual.get_objectives()[objective_index])
+                .fold(f64::MIN, f64::max);
+            let min_objective_value = sorted_population
+                .iter()
+                .flatten()
+                .map(|individual| individual.get_objectives()[objective_index])
+                .fold(f64::MAX, f64::min);
 
-        // calculate distance based on overall deviation fitness
-        sortable_individuals.sort_by(|a, b|
-            a.1.get_objectives().2.partial_cmp(&b.1.get_objectives().2).unwrap()
-        );
-        // get minimum and maximum overall deviation fitness
-        let max_overall_deviation_fitness = sorted_population[sorted_population.len() - 1]
-            .iter()
-            .max_by(|a, b| a.get_objectives().2.partial_cmp(&b.get_objectives().2).unwrap())
-            .unwrap()
-            .get_objectives().2;
-        let min_overall_deviation_fitness = sorted_population[0]
-            .iter()
-            .min_by(|a, b| a.get_objectives().2.partial_cmp(&b.get_objectives().2).unwrap())
-            .unwrap()
-            .get_objectives().2;
-        // assign the distance to the first and last individual
-        sortable_individuals[0].2 = f64::INFINITY;
-        sortable_individuals[sorted_population[i].len() - 1].2 = f64::INFINITY;
-        for j in 1..sortable_individuals.len() - 1 {
-            sortable_individuals[j].2 +=
-                (sortable_individuals[j + 1].1.get_objectives().2 -
-                    sortable_individuals[j - 1].1.get_objectives().2) /
-                (max_overall_deviation_fitness - min_overall_deviation_fitness);
+            // assign the distance to the first and last individual
+            sortable_individuals[0].2 = f64::INFINITY;
+            sortable_individuals[sorted_population[i].len() - 1].2 = f64::INFINITY;
+            if max_objective_value == min_objective_value {
+                continue;
+            }
+            for j in 1..sortable_individuals.len() - 1 {
+                sortable_individuals[j].2 +=
+                    (sortable_individuals[j + 1].1.get_objectives()[objective_index] -
+                        sortable_individuals[j - 1].1.get_objectives()[objective_index]) /
+                    (max_objective_value - min_objective_value);
+            }
         }
 
         // sort by distance highest to lowest
@@ -147,10 +106,15 @@ fn nsga_2_selection(population: &Population, population_size: usize) -> Populati
 fn roulette_wheel_weighted(population: &Population, population_size: usize) -> Population {
     // Create a new population
     let mut new_population: Population = Vec::with_capacity(population_size);
+    // If there are feasible individuals, infeasible ones can't be selected
+    let has_feasible_individual = population.iter().any(|individual| individual.is_feasible());
+    let is_candidate = |individual: &Individual| {
+        !has_feasible_individual || individual.is_feasible()
+    };
     // Calculate the sum of the fitness values
     let mut minimum_fitness = f64::INFINITY;
     let mut maximum_fitness = f64::NEG_INFINITY;
-    for individual in population.iter() {
+    for individual in population.iter().filter(|individual| is_candidate(individual)) {
         let fitness = individual.get_fitness();
         if fitness < minimum_fitness {
             minimum_fitness = fitness;
@@ -159,7 +123,7 @@ fn roulette_wheel_weighted(population: &Population, population_size: usize) -> P
             maximum_fitness = fitness;
         }
     }
-    if minimum_fitness == maximum_fitness {
+    if minimum_fitness == maximum_fitness && population.iter().all(is_candidate) {
         println!("All individuals have the same fitness value. -> Returning the population as is.");
         return population.clone();
     }
@@ -168,7 +132,13 @@ fn roulette_wheel_weighted(population: &Population, population_size: usize) -> P
     let mut probabilitiy_sum = 0.0;
     for individual in population.iter() {
         let fitness = individual.get_fitness();
-        let probability = (fitness - minimum_fitness) / (maximum_fitness - minimum_fitness);
+        let probability = if !is_candidate(individual) {
+            0.0
+        } else if minimum_fitness == maximum_fitness {
+            1.0
+        } else {
+            (fitness - minimum_fitness) / (maximum_fitness - minimum_fitness)
+        };
         probabilities.push(probability);
         probabilitiy_sum += probability;
     }
@@ -203,7 +173,7 @@ fn tournament_weighted(
             tournament.push(population[index].clone());
         }
 
-        tournament.sort_by(|a, b| b.get_fitness().partial_cmp(&a.get_fitness()).unwrap());
+        tournament.sort_by(|a, b| b.compare_weighted_fitness(a));
         let selected_individual = if rng.gen::<f64>() < tournament_probability {
             &tournament[0]
         } else {
@@ -0,0 +1,226 @@
+use crate::distance::{ color_distance, ColorMap, DistanceMap, DistanceMetric };
+
+// every pixel has the label of its superpixel, indexed by [row][column]
+pub type LabelMap = Vec<Vec<usize>>;
+
+/**
+ * Relabels the label map so every 4-connected component has its own consecutive label. Components with less pixels
+ * than the minimum size take the label of an adjacent, already relabeled component, like in the original SLIC paper.
+ */
+fn enforce_connectivity(label_map: &LabelMap, minimum_size: usize) -> LabelMap {
+    let height = label_map.len();
+    let width = label_map[0].len();
+    let mut new_label_map = vec![vec![usize::MAX; width]; height];
+    let mut next_label = 0;
+
+    for start_row in 0..height {
+        for start_column in 0..width {
+            if new_label_map[start_row][start_column] != usize::MAX {
+                continue;
+            }
+            let label = label_map[start_row][start_column];
+            let mut component = vec![(start_row, start_column)];
+            let mut adjacent_label = None;
+            new_label_map[start_row][start_column] = next_label;
+
+            let mut current = 0;
+            while current < component.len() {
+                let (row, column) = component[current];
+                current += 1;
+                for (row_adjustment, column_adjustment) in [
+                    (-1, 0),
+                    (1, 0),
+                    (0, -1),
+                    (0, 1),
+                ] {
+                    let new_row = ((row as isize) + row_adjustment) as usize;
+                    let new_column = ((column as isize) + column_adjustment) as usize;
+                    if new_row >= height || new_column >= width {
+                        continue;
+                    }
+                    let new_label = new_label_map[new_row][new_column];
+                    if new_label != usize::MAX && new_label != next_label && adjacent_label.is_none() {
+                        adjacent_label = Some(new_label);
+                    }
+                    if new_label == usize::MAX && label_map[new_row][new_column] == label {
+                        new_label_map[new_row][new_column] = next_label;
+                        component.push((new_row, new_column));
+                    }
+                }
+            }
+
+            match adjacent_label {
+                Some(adjacent_label) if component.len() < minimum_size => {
+                    for (row, column) in component {
+                        new_label_map[row][column] = adjacent_label;
+                    }
+                }
+                _ => {
+                    next_label += 1;
+                }
+            }
+        }
+    }
+    new_label_map
+}
+
+/**
+ * Simple linear iterative clustering. The cluster centers start on a regular grid and every pixel is assigned to the
+ * closest center within twice the grid interval, where the distance combines the color distance and the spatial
+ * distance weighted by the compactness.
+ */
+pub fn get_slic_label_map(
+    color_map: &ColorMap,
+    distance_metric: DistanceMetric,
+    number_of_superpixels: usize,
+    compactness: f64,
+    number_of_iterations: usize
+) -> LabelMap {
+    let height = color_map.len();
+    let width = color_map[0].len();
+    let grid_interval = (((width * height) as f64) / (number_of_superpixels.max(1) as f64))
+        .sqrt()
+        .max(1.0);
+
+    // (row, column, color) of every cluster center
+    let mut centers: Vec<(f64, f64, (f64, f64, f64))> = vec![];
+    let mut row = grid_interval / 2.0;
+    while row < (height as f64) {
+        let mut column = grid_interval / 2.0;
+        while column < (width as f64) {
+            centers.push((row, column, color_map[row as usize][column as usize]));
+            column += grid_interval;
+        }
+        row += grid_interval;
+    }
+
+    let mut label_map = vec![vec![0; width]; height];
+    for _ in 0..number_of_iterations {
+        let mut distances = vec![vec![f64::INFINITY; width]; height];
+        for (label, (center_row, center_column, center_color)) in centers.iter().enumerate() {
+            let top = (center_row - 2.0 * grid_interval).max(0.0) as usize;
+            let bottom = ((center_row + 2.0 * grid_interval) as usize).min(height - 1);
+            let left = (center_column - 2.0 * grid_interval).max(0.0) as usize;
+            let right = ((center_column + 2.0 * grid_interval) as usize).min(width - 1);
+            for row in top..=bottom {
+                for column in left..=right {
+                    let color = color_distance(&color_map[row][column], center_color, distance_metric);
+                    let spatial = ((row as f64) - center_row).hypot((column as f64) - center_column);
+                    let distance = (
+                        color.powi(2) + (spatial / grid_interval).powi(2) * compactness.powi(2)
+                    ).sqrt();
+                    if distance < distances[row][column] {
+                        distances[row][column] = distance;
+                        label_map[row][column] = label;
+                    }
+                }
+            }
+        }
+
+        // move every center to the mean of its pixels
+        let mut sums = vec![(0.0, 0.0, (0.0, 0.0, 0.0), 0); centers.len()];
+        for row in 0..height {
+            for column in 0..width {
+                let sum = &mut sums[label_map[row][column]];
+                let color = color_map[row][column];
+                sum.0 += row as f64;
+                sum.1 += column as f64;
+                sum.2.0 += color.0;
+                sum.2.1 += color.1;
+                sum.2.2 += color.2;
+                sum.3 += 1;
+            }
+        }
+        for (center, sum) in centers.iter_mut().zip(sums.iter()) {
+            if sum.3 == 0 {
+                continue;
+            }
+            let count = sum.3 as f64;
+            *center = (sum.0 / count, sum.1 / count, (sum.2.0 / count, sum.2.1 / count, sum.2.2 / count));
+        }
+    }
+
+    let minimum_size = ((grid_interval * grid_interval) / 4.0) as usize;
+    enforce_connectivity(&label_map, minimum_size)
+}
+
+fn find_root(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // path compression
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+/**
+ * Graph based segmentation by Felzenszwalb and Huttenlocher on the 4-neighborhood graph of the image.
+ * Two components are merged if the edge between them is not heavier than the internal difference of both components
+ * plus scale / size. Components which are still smaller than the minimum segment size are merged afterwards.
+ */
+pub fn get_felzenszwalb_label_map(
+    distance_map: &DistanceMap,
+    width: usize,
+    height: usize,
+    scale: f64,
+    minimum_segment_size: usize
+) -> LabelMap {
+    // (weight, pixel, neighbor) for the right and lower neighbor of every pixel
+    let mut edges: Vec<(f64, usize, usize)> = Vec::with_capacity(2 * width * height);
+    for (row, distance_row) in distance_map.iter().enumerate() {
+        for (column, distances) in distance_row.iter().enumerate() {
+            let index = row * width + column;
+            if column < width - 1 {
+                edges.push((distances[3][4], index, index + 1));
+            }
+            if row < height - 1 {
+                edges.push((distances[4][3], index, index + width));
+            }
+        }
+    }
+    edges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut parents: Vec<usize> = (0..width * height).collect();
+    let mut sizes = vec![1; width * height];
+    let mut internal_differences = vec![0.0; width * height];
+
+    for (weight, pixel, neighbor) in edges.iter() {
+        let root_a = find_root(&mut parents, *pixel);
+        let root_b = find_root(&mut parents, *neighbor);
+        if root_a == root_b {
+            continue;
+        }
+        if
+            *weight <= internal_differences[root_a] + scale / (sizes[root_a] as f64) &&
+            *weight <= internal_differences[root_b] + scale / (sizes[root_b] as f64)
+        {
+            parents[root_b] = root_a;
+            sizes[root_a] += sizes[root_b];
+            // the edges are sorted, so the current edge is the heaviest of the minimum spanning tree of the component
+            internal_differences[root_a] = *weight;
+        }
+    }
+
+    for (_, pixel, neighbor) in edges.iter() {
+        let root_a = find_root(&mut parents, *pixel);
+        let root_b = find_root(&mut parents, *neighbor);
+        if root_a != root_b && (sizes[root_a] < minimum_segment_size || sizes[root_b] < minimum_segment_size) {
+            parents[root_b] = root_a;
+            sizes[root_a] += sizes[root_b];
+        }
+    }
+
+    (0..height)
+        .map(|row| {
+            (0..width)
+                .map(|column| find_root(&mut parents, row * width + column))
+                .collect()
+        })
+        .collect()
+}
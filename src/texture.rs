@@ -0,0 +1,196 @@
+use image::{ GrayImage, RgbImage };
+
+// every pixel has a small feature vector describing the texture of its neighborhood, indexed by [row][column]
+pub type TextureMap = Vec<Vec<Vec<f64>>>;
+
+pub const NUMBER_OF_TEXTURE_FEATURES: usize = 3;
+
+/**
+ * Sums up a per pixel value over a square window using a summed area table
+ */
+fn get_window_means(values: &[Vec<f64>], window_radius: usize) -> Vec<Vec<f64>> {
+    let height = values.len();
+    let width = values[0].len();
+
+    let mut summed_area_table = vec![vec![0.0; width + 1]; height + 1];
+    for row in 0..height {
+        for column in 0..width {
+            summed_area_table[row + 1][column + 1] =
+                values[row][column] +
+                summed_area_table[row][column + 1] +
+                summed_area_table[row + 1][column] -
+                summed_area_table[row][column];
+        }
+    }
+
+    let mut means = vec![vec![0.0; width]; height];
+    for (row, means_row) in means.iter_mut().enumerate() {
+        for (column, mean) in means_row.iter_mut().enumerate() {
+            let top = row.saturating_sub(window_radius);
+            let bottom = (row + window_radius + 1).min(height);
+            let left = column.saturating_sub(window_radius);
+            let right = (column + window_radius + 1).min(width);
+            let sum =
+                summed_area_table[bottom][right] -
+                summed_area_table[top][right] -
+                summed_area_table[bottom][left] +
+                summed_area_table[top][left];
+            *mean = sum / (((bottom - top) * (right - left)) as f64);
+        }
+    }
+    means
+}
+
+/**
+ * Calculates the gradient magnitude of every pixel with the sobel operator
+ */
+fn get_gradient_energy(gray_image: &GrayImage) -> Vec<Vec<f64>> {
+    let (width, height) = (gray_image.width() as i64, gray_image.height() as i64);
+    let intensity = |column: i64, row: i64| {
+        gray_image.get_pixel(
+            column.clamp(0, width - 1) as u32,
+            row.clamp(0, height - 1) as u32
+        )[0] as f64
+    };
+
+    let mut gradient_energy = vec![vec![0.0; width as usize]; height as usize];
+    for row in 0..height {
+        for column in 0..width {
+            let horizontal =
+                intensity(column + 1, row - 1) +
+                2.0 * intensity(column + 1, row) +
+                intensity(column + 1, row + 1) -
+                intensity(column - 1, row - 1) -
+                2.0 * intensity(column - 1, row) -
+                intensity(column - 1, row + 1);
+            let vertical =
+                intensity(column - 1, row + 1) +
+                2.0 * intensity(column, row + 1) +
+                intensity(column + 1, row + 1) -
+                intensity(column - 1, row - 1) -
+                2.0 * intensity(column, row - 1) -
+                intensity(column + 1, row - 1);
+            gradient_energy[row as usize][column as usize] = (
+                horizontal.powi(2) + vertical.powi(2)
+            ).sqrt();
+        }
+    }
+    gradient_energy
+}
+
+/**
+ * Counts the 0/1 transitions of the 8 neighbor local binary pattern of every pixel.
+ * Flat regions and straight edges have few transitions, noisy textures like grass have many.
+ */
+fn get_local_binary_pattern_transitions(gray_image: &GrayImage) -> Vec<Vec<f64>> {
+    let (width, height) = (gray_image.width() as i64, gray_image.height() as i64);
+    let mut transitions = vec![vec![0.0; width as usize]; height as usize];
+
+    // the neighbors in clockwise order, so consecutive entries are adjacent in the pattern
+    let neighbors: [(i64, i64); 8] = [
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+    ];
+
+    for row in 0..height {
+        for column in 0..width {
+            let center = gray_image.get_pixel(column as u32, row as u32)[0];
+            let pattern: Vec<bool> = neighbors
+                .iter()
+                .map(|(x_offset, y_offset)| {
+                    let neighbor = gray_image.get_pixel(
+                        (column + x_offset).clamp(0, width - 1) as u32,
+                        (row + y_offset).clamp(0, height - 1) as u32
+                    )[0];
+                    neighbor >= center
+                })
+                .collect();
+
+            let mut number_of_transitions = 0;
+            for index in 0..pattern.len() {
+                if pattern[index] != pattern[(index + 1) % pattern.len()] {
+                    number_of_transitions += 1;
+                }
+            }
+            transitions[row as usize][column as usize] = number_of_transitions as f64;
+        }
+    }
+    transitions
+}
+
+/**
+ * Calculates per pixel texture features over a window around the pixel:
+ * mean gradient energy, standard deviation of the intensity and mean number of LBP transitions.
+ * Every feature is scaled to the range 0 to 255 so it is comparable with RGB distances.
+ */
+pub fn calculate_texture_map(rgb_image: &RgbImage, window_radius: usize) -> TextureMap {
+    let gray_image = image::imageops::grayscale(rgb_image);
+    let (width, height) = (gray_image.width() as usize, gray_image.height() as usize);
+
+    let mut intensity = vec![vec![0.0; width]; height];
+    let mut squared_intensity = vec![vec![0.0; width]; height];
+    for (column, row, pixel) in gray_image.enumerate_pixels() {
+        intensity[row as usize][column as usize] = pixel[0] as f64;
+        squared_intensity[row as usize][column as usize] = (pixel[0] as f64).powi(2);
+    }
+
+    let mean_gradient_energy = get_window_means(&get_gradient_energy(&gray_image), window_radius);
+    let mean_intensity = get_window_means(&intensity, window_radius);
+    let mean_squared_intensity = get_window_means(&squared_intensity, window_radius);
+    let mean_transitions = get_window_means(
+        &get_local_binary_pattern_transitions(&gray_image),
+        window_radius
+    );
+
+    let mut texture_map: TextureMap =
+        vec![
+            vec![vec![0.0; NUMBER_OF_TEXTURE_FEATURES]; width];
+            height
+        ];
+    let mut maximum_per_feature = [0.0f64; NUMBER_OF_TEXTURE_FEATURES];
+    for row in 0..height {
+        for column in 0..width {
+            let standard_deviation = (
+                mean_squared_intensity[row][column] - mean_intensity[row][column].powi(2)
+            )
+                .max(0.0)
+                .sqrt();
+            let features = [
+                mean_gradient_energy[row][column],
+                standard_deviation,
+                mean_transitions[row][column],
+            ];
+            for (index, feature) in features.iter().enumerate() {
+                maximum_per_feature[index] = maximum_per_feature[index].max(*feature);
+            }
+            texture_map[row][column] = features.to_vec();
+        }
+    }
+
+    // Scale the features between 0 and 255
+    for texture_row in texture_map.iter_mut() {
+        for texture in texture_row.iter_mut() {
+            for (feature, maximum) in texture.iter_mut().zip(maximum_per_feature.iter()) {
+                if *maximum > 0.0 {
+                    *feature *= 255.0 / maximum;
+                }
+            }
+        }
+    }
+    texture_map
+}
+
+pub fn texture_distance(texture_a: &[f64], texture_b: &[f64]) -> f64 {
+    texture_a
+        .iter()
+        .zip(texture_b.iter())
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
@@ -1,10 +1,22 @@
 use image::RgbImage;
 use rand::{ thread_rng, Rng };
+#[cfg(feature = "gui")]
 use show_image::{ create_window, event };
 
-use crate::{ global_data::GlobalData, individual::Individual };
+#[cfg(feature = "gui")]
+use crate::config::Config;
 
-fn combine_images(img1: &RgbImage, img2: &RgbImage) -> RgbImage {
+use crate::{
+    config::FunctionConfig,
+    global_data::GlobalData,
+    individual::Individual,
+    weighted_sampler::WeightedSampler,
+};
+
+/**
+ * Places the second image to the right of the first one
+ */
+pub fn combine_images(img1: &RgbImage, img2: &RgbImage) -> RgbImage {
     let (width1, height) = img1.dimensions();
     let (width2, _) = img2.dimensions();
     let combined_width = width1 + width2;
@@ -25,6 +37,7 @@ fn combine_images(img1: &RgbImage, img2: &RgbImage) -> RgbImage {
     combined_image
 }
 
+#[cfg(feature = "gui")]
 #[allow(dead_code)]
 pub fn show(image: &RgbImage) {
     // Create a window and display the image.
@@ -46,21 +59,20 @@ pub fn show(image: &RgbImage) {
     }
 }
 
+#[cfg(feature = "gui")]
 #[allow(dead_code)]
-pub fn show_with_data(image: &RgbImage, individual: &Individual, global_data: &GlobalData) {
+pub fn show_with_data(image: &RgbImage, individual: &Individual, config: &Config, global_data: &GlobalData) {
     // Create a window and display the image.
-    let multi_fitness = individual.get_objectives();
-    let fitness = individual.get_fitness();
-    let title = format!(
-        "Edge Value Fitness: {} Connectivity Fitness: {} Overall Deviation Fitness: {} Weighted Fitness: {}",
-        multi_fitness.0,
-        multi_fitness.1,
-        multi_fitness.2,
-        fitness
-    );
+    let mut title = String::new();
+    for (weighted_objective, value) in global_data.objectives
+        .iter()
+        .zip(individual.get_objectives().iter()) {
+        title += &format!("{}: {} ", weighted_objective.objective.name(), value);
+    }
+    title += &format!("Weighted Fitness: {}", individual.get_fitness());
 
     let black_white_image = individual.get_segment_border_image(global_data);
-    let segment_image = individual.get_segments_image(global_data);
+    let segment_image = individual.get_segments_image(config, global_data);
     let window = create_window(title, Default::default()).unwrap();
     let combined = combine_images(&black_white_image, &segment_image);
     window.set_image("image-001", combine_images(&image.clone(), &combined)).unwrap();
@@ -81,18 +93,164 @@ pub fn show_with_data(image: &RgbImage, individual: &Individual, global_data: &G
 }
 
 pub fn get_edge_weighted_random_pixel_index(global_data: &GlobalData) -> usize {
-    // Pick a random number between 0 and 1
-    let random_number = thread_rng().gen_range(0.0..=1.0);
-
-    // Accumulate weights until exceeding the random number
-    let mut current_sum = 0.0;
-    for (index, weight) in global_data.pixel_weights.iter().enumerate() {
-        current_sum += *weight;
-        if current_sum >= random_number {
-            return index;
+    global_data.pixel_sampler.sample()
+}
+
+/**
+ * Creates a sampler for the pixels of the individual. The boundary weight is the share of the probability which is
+ * spread equally over the pixels on the segment boundaries, the rest follows the edge weights.
+ * The border of the image is not a segment boundary.
+ */
+pub fn get_boundary_pixel_sampler(
+    individual: &Individual,
+    boundary_weight: f64,
+    global_data: &GlobalData
+) -> WeightedSampler {
+    let border_map = individual.get_border_map(global_data);
+    let is_boundary = |index: usize| {
+        let row = index / global_data.width;
+        let column = index % global_data.width;
+        row > 0 &&
+            row < global_data.height - 1 &&
+            column > 0 &&
+            column < global_data.width - 1 &&
+            border_map[row][column] == 0
+    };
+    let number_of_pixels = global_data.width * global_data.height;
+    let number_of_boundary_pixels = (0..number_of_pixels).filter(|index| is_boundary(*index)).count();
+    let total_edge_weight = global_data.pixel_sampler.get_total_weight();
+
+    let weights: Vec<f64> = (0..number_of_pixels)
+        .map(|index| {
+            let mut weight = 0.0;
+            if total_edge_weight > 0.0 {
+                weight += (1.0 - boundary_weight) * (global_data.pixel_sampler.get_weight(index) / total_edge_weight);
+            }
+            if number_of_boundary_pixels > 0 && is_boundary(index) {
+                weight += boundary_weight / (number_of_boundary_pixels as f64);
+            }
+            weight
+        })
+        .collect();
+    WeightedSampler::new(&weights)
+}
+
+/**
+ * Samples a standard normal distributed number with the Box-Muller transform
+ */
+pub fn get_standard_normal_sample() -> f64 {
+    let mut rng = thread_rng();
+    let uniform_a: f64 = rng.gen_range(f64::MIN_POSITIVE..1.0);
+    let uniform_b: f64 = rng.gen_range(0.0..1.0);
+    (-2.0 * uniform_a.ln()).sqrt() * (2.0 * std::f64::consts::PI * uniform_b).cos()
+}
+
+/**
+ * Samples the number of successes of independent trials with the same probability.
+ * The gaps between successes are geometrically distributed, so only the successes need a random number.
+ */
+pub fn get_binomial_sample(number_of_trials: usize, probability: f64) -> usize {
+    if probability <= 0.0 {
+        return 0;
+    }
+    if probability >= 1.0 {
+        return number_of_trials;
+    }
+    let mut rng = thread_rng();
+    let mut number_of_successes = 0;
+    let mut position = 0.0;
+    loop {
+        let random_number: f64 = rng.gen_range(f64::MIN_POSITIVE..1.0);
+        position += (random_number.ln() / (1.0 - probability).ln()).floor() + 1.0;
+        if position > (number_of_trials as f64) {
+            return number_of_successes;
         }
+        number_of_successes += 1;
     }
+}
 
-    // In case of rounding errors, return the last pixel
-    (global_data.width * global_data.height - 1) as usize
+/**
+ * Returns how often the operator is applied to every individual, depending on the probability mode of the operator:
+ * "count" applies it ceil(population size * probability) times to random individuals, "per_individual" applies it
+ * once to every individual with the probability and "per_gene" applies it once per gene with the probability.
+ */
+pub fn get_number_of_applications(
+    function_config: &FunctionConfig,
+    population_size: usize,
+    genome_length: usize
+) -> Vec<usize> {
+    let mut rng = thread_rng();
+    let probability = function_config.probability.unwrap();
+    let mut number_of_applications = vec![0; population_size];
+    match function_config.probability_mode.as_deref().unwrap_or("count") {
+        "count" => {
+            let number_of_operations = ((population_size as f64) * probability).ceil() as usize;
+            for _ in 0..number_of_operations {
+                number_of_applications[rng.gen_range(0..population_size)] += 1;
+            }
+        }
+        "per_individual" => {
+            for applications in number_of_applications.iter_mut() {
+                if rng.gen::<f64>() < probability {
+                    *applications = 1;
+                }
+            }
+        }
+        "per_gene" => {
+            for applications in number_of_applications.iter_mut() {
+                *applications = get_binomial_sample(genome_length, probability);
+            }
+        }
+        _ =>
+            panic!(
+                "Didn't have an Implementation for probability mode: {:?}",
+                function_config.probability_mode
+            ),
+    }
+    number_of_applications
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ Rgb, RgbImage };
+
+    use super::*;
+    use crate::{ config::get_test_config, global_data::ImageData, individual::get_genome_from_label_map };
+
+    #[test]
+    fn boundary_pixel_sampler_spreads_the_boundary_weight_over_the_segment_boundaries() {
+        // a left and a right half, the boundary pixels are the first column of the right half
+        let (width, height) = (6, 5);
+        let label_map: Vec<Vec<usize>> = (0..height)
+            .map(|_| (0..width).map(|column| if column < 3 { 1 } else { 2 }).collect())
+            .collect();
+        let rgb_image = RgbImage::from_fn(width as u32, height as u32, |column, row| {
+            Rgb([(column * 40) as u8, (row * 50) as u8, 100])
+        });
+        let config = get_test_config();
+        let image_data = ImageData::new(rgb_image, &config, None);
+        let global_data = image_data.get_global_data();
+        let individual = Individual::new_with_genome(&get_genome_from_label_map(&label_map));
+
+        let boundary_weight = 0.6;
+        let sampler = get_boundary_pixel_sampler(&individual, boundary_weight, &global_data);
+        let total_edge_weight = global_data.pixel_sampler.get_total_weight();
+        assert!(total_edge_weight > 0.0);
+        let mut boundary_mass = 0.0;
+        for index in 0..width * height {
+            let (row, column) = (index / width, index % width);
+            let base_weight =
+                (1.0 - boundary_weight) * (global_data.pixel_sampler.get_weight(index) / total_edge_weight);
+            // the segment boundary in the first and last row is on the border of the image
+            if column == 3 && row > 0 && row < height - 1 {
+                let boundary_share = sampler.get_weight(index) - base_weight;
+                assert!((boundary_share - boundary_weight / 3.0).abs() < 1e-9, "pixel ({}, {})", row, column);
+                boundary_mass += boundary_share;
+            } else {
+                assert!((sampler.get_weight(index) - base_weight).abs() < 1e-9, "pixel ({}, {})", row, column);
+            }
+        }
+        assert!((boundary_mass - boundary_weight).abs() < 1e-9);
+        assert!((sampler.get_total_weight() - 1.0).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,106 @@
+use rand::{ thread_rng, Rng };
+
+/**
+ * Samples indices proportional to their weights in O(log n). The cumulative weights are calculated once and every
+ * sample is a binary search for a random number in them.
+ */
+pub struct WeightedSampler {
+    cumulative_weights: Vec<f64>,
+}
+
+impl WeightedSampler {
+    // The weights don't need to be normalized, but there has to be at least one
+    pub fn new(weights: &[f64]) -> WeightedSampler {
+        if weights.is_empty() {
+            panic!("The weighted sampler needs at least one weight to sample from.");
+        }
+        let mut cumulative_weights = Vec::with_capacity(weights.len());
+        let mut current_sum = 0.0;
+        for weight in weights.iter() {
+            current_sum += weight.max(0.0);
+            cumulative_weights.push(current_sum);
+        }
+        WeightedSampler { cumulative_weights }
+    }
+
+    pub fn get_total_weight(&self) -> f64 {
+        *self.cumulative_weights.last().unwrap_or(&0.0)
+    }
+
+    pub fn get_weight(&self, index: usize) -> f64 {
+        if index == 0 {
+            self.cumulative_weights[0]
+        } else {
+            self.cumulative_weights[index] - self.cumulative_weights[index - 1]
+        }
+    }
+
+    /**
+     * Returns a random index. Indices with a weight of 0 are never picked, unless all weights are 0
+     */
+    pub fn sample(&self) -> usize {
+        let mut rng = thread_rng();
+        let total_weight = self.get_total_weight();
+        if total_weight <= 0.0 {
+            return rng.gen_range(0..self.cumulative_weights.len());
+        }
+        let random_number = rng.gen_range(0.0..total_weight);
+        // first index whose cumulative weight exceeds the random number
+        self.cumulative_weights
+            .partition_point(|cumulative_weight| *cumulative_weight <= random_number)
+            .min(self.cumulative_weights.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_never_returns_indices_without_weight() {
+        let weights = vec![0.0, 3.0, 0.0, 1.0, 0.0, 0.0, 6.0, 0.0];
+        let sampler = WeightedSampler::new(&weights);
+        for _ in 0..10000 {
+            assert!(weights[sampler.sample()] > 0.0);
+        }
+    }
+
+    #[test]
+    fn sample_frequencies_match_the_weights() {
+        let weights = vec![0.0, 3.0, 0.0, 1.0, 6.0];
+        let sampler = WeightedSampler::new(&weights);
+        let number_of_samples = 100000;
+        let mut counts = vec![0; weights.len()];
+        for _ in 0..number_of_samples {
+            counts[sampler.sample()] += 1;
+        }
+        for (count, weight) in counts.iter().zip(weights.iter()) {
+            let frequency = (*count as f64) / (number_of_samples as f64);
+            assert!((frequency - weight / 10.0).abs() < 0.01, "frequency {} for weight {}", frequency, weight);
+        }
+    }
+
+    #[test]
+    fn sample_is_uniform_without_weights() {
+        let sampler = WeightedSampler::new(&[0.0; 4]);
+        for _ in 0..100 {
+            assert!(sampler.sample() < 4);
+        }
+    }
+
+    #[test]
+    fn get_weight_returns_the_weights() {
+        let weights = vec![0.5, 0.0, 2.25, 1.0, 0.0, 3.75];
+        let sampler = WeightedSampler::new(&weights);
+        for (index, weight) in weights.iter().enumerate() {
+            assert!((sampler.get_weight(index) - weight).abs() < 1e-12);
+        }
+        assert!((sampler.get_total_weight() - 7.5).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "needs at least one weight")]
+    fn new_rejects_empty_weights() {
+        WeightedSampler::new(&[]);
+    }
+}